@@ -0,0 +1,180 @@
+//! Generated models kept on disk, so asking for the same one again is quick.
+//! Models are always written to a file as they're generated, even when there's
+//! nowhere to keep them, so they never have to fit in memory.
+
+use log::warn;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempfile::{Builder, NamedTempFile};
+
+/// Only keeps things if there's a directory to keep them in, and only as much
+/// as fits in `max_bytes`, for up to `max_age`.
+pub struct Cache {
+    dir: Option<PathBuf>,
+    max_bytes: u64,
+    max_age: Duration,
+}
+
+impl Cache {
+    pub fn new(dir: Option<PathBuf>, max_bytes: u64, max_age: Duration) -> Self {
+        let dir = dir.filter(|dir| match fs::create_dir_all(dir) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Not caching, couldn't create {}: {}", dir.display(), e);
+                false
+            }
+        });
+        let cache = Cache {
+            dir,
+            max_bytes,
+            max_age,
+        };
+        cache.trim();
+        cache
+    }
+
+    pub fn get(&self, key: &str) -> Option<File> {
+        File::open(self.dir.as_ref()?.join(key)).ok()
+    }
+
+    /// Everything kept for `key`, for small files that go along with a model.
+    pub fn read(&self, key: &str) -> Option<Vec<u8>> {
+        let mut bytes = vec![];
+        self.get(key)?.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    /// Keeps `bytes` for `key`, if there's somewhere to keep them.
+    pub fn write(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        if self.dir.is_none() {
+            return Ok(());
+        }
+        let entry = self.entry(key)?;
+        match entry.writer().and_then(|mut file| file.write_all(bytes)) {
+            Ok(()) => entry.finish().map(drop),
+            Err(e) => {
+                entry.discard();
+                Err(e)
+            }
+        }
+    }
+
+    /// Deletes anything older than `max_age`, and then the oldest files until
+    /// the rest fit in `max_bytes`.
+    fn trim(&self) {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Couldn't read the cache in {}: {}", dir.display(), e);
+                return;
+            }
+        };
+        let now = SystemTime::now();
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                Some((metadata.modified().unwrap_or(now), metadata.len(), entry.path()))
+            })
+            .collect();
+        // newest first
+        files.sort_by_key(|&(modified, _, _)| Reverse(modified));
+        let mut total = 0;
+        for (modified, size, path) in files {
+            let age = now.duration_since(modified).unwrap_or_default();
+            // files still being written only go if they've been left behind
+            let partial = path.extension() == Some(OsStr::new("partial"));
+            if !partial {
+                total += size;
+            }
+            if (total > self.max_bytes && !partial) || age > self.max_age {
+                if let Err(e) = fs::remove_file(&path) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        warn!("Couldn't remove {}: {}", path.display(), e);
+                    }
+                }
+                if !partial {
+                    total -= size;
+                }
+            }
+        }
+    }
+
+    /// A new file to write the model for `key` to.
+    pub fn entry(&self, key: &str) -> io::Result<Entry<'_>> {
+        let file = match &self.dir {
+            Some(dir) => Partial::Named(
+                Builder::new().prefix(key).suffix(".partial").tempfile_in(dir)?,
+                dir.join(key),
+            ),
+            None => Partial::Unnamed(tempfile::tempfile()?),
+        };
+        Ok(Entry { cache: self, file })
+    }
+}
+
+/// A file being written. It has a different name until it's finished, so a half
+/// written one is never read.
+pub struct Entry<'a> {
+    cache: &'a Cache,
+    file: Partial,
+}
+
+enum Partial {
+    /// to be kept at the path once it's finished
+    Named(NamedTempFile, PathBuf),
+    /// with no name, so it's deleted as soon as it's closed
+    Unnamed(File),
+}
+
+impl Entry<'_> {
+    /// Another handle to the file, to write the model with.
+    pub fn writer(&self) -> io::Result<File> {
+        match &self.file {
+            Partial::Named(file, _) => file.as_file().try_clone(),
+            Partial::Unnamed(file) => file.try_clone(),
+        }
+    }
+
+    /// Keeps the file if there's a cache, making room for it, and gives it back
+    /// ready to be read. If there isn't, it's deleted once it's closed.
+    pub fn finish(self) -> io::Result<File> {
+        let mut file = match self.file {
+            Partial::Named(file, path) => {
+                let file = file.persist(path).map_err(|e| e.error)?;
+                self.cache.trim();
+                file
+            }
+            Partial::Unnamed(file) => file,
+        };
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+
+    pub fn discard(self) {
+        if let Partial::Named(file, _) = self.file {
+            let path = file.path().to_path_buf();
+            if let Err(e) = file.close() {
+                warn!("Couldn't remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// A name for everything that goes into making a model, which is the same
+/// whenever the inputs are. Each kind of file made from them adds its own
+/// extension.
+pub fn key<T: Serialize>(inputs: &T) -> String {
+    let bytes = serde_json::to_vec(inputs).unwrap();
+    format!("{:x}", Sha256::digest(&bytes))
+}
@@ -0,0 +1,146 @@
+//! The server's settings, read from a TOML file and then overridden by any
+//! `FRACTAL_` environment variables.
+
+use crate::cache::Cache;
+use crate::limits::Limits;
+use crate::Params;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml::Value;
+
+/// Where the config is read from if `FRACTAL_CONFIG` isn't set. It's fine for
+/// this not to exist.
+const DEFAULT_PATH: &str = "fractal.toml";
+const ENV_PREFIX: &str = "FRACTAL_";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    /// the built web client
+    pub static_dir: PathBuf,
+    /// one of off, error, warn, info, debug or trace
+    pub log_level: String,
+    /// also log to this file, as well as the terminal
+    pub log_file: Option<PathBuf>,
+    /// keep generated models here, so the same request isn't generated twice
+    pub cache_dir: Option<PathBuf>,
+    /// the most the cache can hold, after which the oldest files are deleted
+    pub cache_max_megabytes: u64,
+    /// how long anything is kept in the cache
+    pub cache_max_age_hours: f64,
+    pub limits: Limits,
+    /// used for any query parameters a request leaves out
+    pub defaults: Params,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: "localhost".to_string(),
+            port: 8000,
+            static_dir: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../web/dist")),
+            log_level: "info".to_string(),
+            log_file: None,
+            cache_dir: None,
+            cache_max_megabytes: 1024,
+            cache_max_age_hours: 24.0 * 7.0,
+            limits: Limits::default(),
+            defaults: Params::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file at `FRACTAL_CONFIG` (or `fractal.toml`, if it's there),
+    /// then applies the environment variables on top.
+    pub fn load() -> Result<Config, String> {
+        let path = env::var(format!("{}CONFIG", ENV_PREFIX)).ok();
+        let mut value = match &path {
+            Some(path) => read(Path::new(path))?,
+            None if Path::new(DEFAULT_PATH).exists() => read(Path::new(DEFAULT_PATH))?,
+            None => Value::Table(Default::default()),
+        };
+        for (key, var) in env::vars() {
+            if key.starts_with(ENV_PREFIX) && key != format!("{}CONFIG", ENV_PREFIX) {
+                override_key(&mut value, &key[ENV_PREFIX.len()..], &var);
+            }
+        }
+        let config: Config = value.try_into().map_err(|e| format!("Invalid config: {}", e))?;
+        config.log_level()?;
+        check_seconds("cache_max_age_hours", config.cache_max_age_hours * 3600.0)?;
+        check_seconds("limits.timeout_seconds", config.limits.timeout_seconds)?;
+        Ok(config)
+    }
+
+    pub fn log_level(&self) -> Result<LevelFilter, String> {
+        self.log_level.parse().map_err(|_| {
+            format!(
+                "Invalid config: log_level has to be one of off, error, warn, info, debug or trace, not \"{}\"",
+                self.log_level
+            )
+        })
+    }
+
+    pub fn cache(&self) -> Cache {
+        Cache::new(
+            self.cache_dir.clone(),
+            self.cache_max_megabytes.saturating_mul(1024 * 1024),
+            Duration::from_secs_f64(self.cache_max_age_hours * 3600.0),
+        )
+    }
+}
+
+// the longest a time in the config can be, so it fits in a `Duration` and a
+// deadline that far off can still be worked out (about 30 years)
+const MAX_SECONDS: f64 = 1e9;
+
+// NaN isn't in the range
+fn check_seconds(name: &str, seconds: f64) -> Result<(), String> {
+    if (0.0..=MAX_SECONDS).contains(&seconds) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid config: {} has to be 0 or more, and no more than about 30 years",
+            name
+        ))
+    }
+}
+
+fn read(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("Couldn't parse {}: {}", path.display(), e))
+}
+
+/// Sets `key` in `config` to `var`. `key` is lower-cased, and `__` separates
+/// the names of nested tables, so `LIMITS__MAX_TRIANGLES` sets
+/// `max_triangles` in `[limits]`. `var` is read as a TOML value if it is one
+/// (so numbers and booleans work), and as a string if it isn't.
+fn override_key(config: &mut Value, key: &str, var: &str) {
+    let parsed = toml::from_str::<Value>(&format!("v = {}", var))
+        .ok()
+        .and_then(|t| t.get("v").cloned())
+        .unwrap_or_else(|| Value::String(var.to_string()));
+
+    let names: Vec<String> = key.split("__").map(|n| n.to_lowercase()).collect();
+    let mut table = config;
+    for name in &names[..names.len() - 1] {
+        table = table
+            .as_table_mut()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert_with(|| Value::Table(Default::default()));
+        if !table.is_table() {
+            *table = Value::Table(Default::default());
+        }
+    }
+    table
+        .as_table_mut()
+        .unwrap()
+        .insert(names[names.len() - 1].clone(), parsed);
+}
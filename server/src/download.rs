@@ -0,0 +1,97 @@
+//! Sending generated models back, compressed if the browser can take it, or
+//! in a zip along with what they were made from.
+
+use flate2::read::GzEncoder;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// The best encoding in the request's `Accept-Encoding` header that we can
+/// do, if there is one.
+pub struct AcceptEncoding(pub Option<Encoding>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for AcceptEncoding {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        Outcome::Success(AcceptEncoding(
+            request
+                .headers()
+                .get_one("Accept-Encoding")
+                .and_then(best_encoding),
+        ))
+    }
+}
+
+/// Picks the encoding the client likes most, out of the ones we have (with
+/// zstd winning ties, as it's quicker), from a header like
+/// `gzip;q=0.8, zstd`.
+fn best_encoding(header: &str) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f64)> = None;
+    for item in header.split(',') {
+        let mut parts = item.split(';').map(|p| p.trim());
+        let name = parts.next().unwrap_or("");
+        let quality = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        let encodings: &[Encoding] = match name {
+            "zstd" => &[Encoding::Zstd],
+            "gzip" | "x-gzip" => &[Encoding::Gzip],
+            "*" => &[Encoding::Zstd, Encoding::Gzip],
+            _ => &[],
+        };
+        for &encoding in encodings {
+            let better = best.map_or(true, |(_, q)| {
+                quality > q || quality == q && encoding == Encoding::Zstd
+            });
+            if quality > 0.0 && better {
+                best = Some((encoding, quality));
+            }
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// `file` compressed as it's read.
+pub fn encode(file: File, encoding: Encoding) -> io::Result<Box<dyn Read + Send>> {
+    Ok(match encoding {
+        Encoding::Zstd => Box::new(zstd::stream::read::Encoder::new(file, 0)?),
+        Encoding::Gzip => Box::new(GzEncoder::new(file, flate2::Compression::default())),
+    })
+}
+
+/// Writes a zip of the model in `stl`, `inputs` which is everything needed to
+/// make it again, and the report on making it if there is one, to `out`.
+pub fn archive(mut stl: File, inputs: &[u8], report: Option<&[u8]>, out: File) -> io::Result<()> {
+    let mut zip = ZipWriter::new(out);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("model.stl", options)?;
+    io::copy(&mut stl, &mut zip)?;
+    zip.start_file("parameters.json", options)?;
+    zip.write_all(inputs)?;
+    if let Some(report) = report {
+        zip.start_file("report.json", options)?;
+        zip.write_all(report)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
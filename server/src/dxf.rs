@@ -0,0 +1,117 @@
+//! Writes layer outlines as an R12 ASCII DXF, which any CAD program can open.
+//! R12 doesn't have LWPOLYLINEs, so each path is a POLYLINE made of VERTEXes,
+//! which is what they replaced, and holds the same things.
+
+use crate::geom::Point2d;
+use crate::outline::{LayerOutline, Part, Path};
+use std::fmt::Write;
+
+/// The colours the DXF layers cycle through (red, yellow, green, cyan, blue
+/// and magenta).
+const COLOURS: [u32; 6] = [1, 2, 3, 4, 5, 6];
+
+// each value goes on the line after its group code
+fn group(dxf: &mut String, code: u32, value: impl std::fmt::Display) {
+    writeln!(dxf, "{}\n{}", code, value).unwrap();
+}
+
+// the name for layer `index` (counting from 1) when there are `per_iteration`
+// layers for each iteration, so the layers added in between are named after
+// the iteration before them and how many on from it they are
+fn layer_name(index: usize, per_iteration: usize) -> String {
+    let (iteration, step) = ((index - 1) / per_iteration + 1, (index - 1) % per_iteration);
+    if step == 0 {
+        format!("ITERATION_{}", iteration)
+    } else {
+        format!("ITERATION_{}_{}", iteration, step)
+    }
+}
+
+fn polyline(dxf: &mut String, layer: &str, z: f64, path: &Path) {
+    group(dxf, 0, "POLYLINE");
+    group(dxf, 8, layer);
+    group(dxf, 66, 1);
+    group(dxf, 10, 0.0);
+    group(dxf, 20, 0.0);
+    group(dxf, 30, z);
+    let closed = path.start.sub(path.end()).norm() < 1e-9 && path.parts.len() > 1;
+    group(dxf, 70, if closed { 1 } else { 0 });
+
+    // the bulge of a vertex says how the path curves on the way to the next
+    // one, as the tangent of a quarter of the angle it goes round
+    let mut vertex = |point: Point2d, bulge: f64| {
+        group(dxf, 0, "VERTEX");
+        group(dxf, 8, layer);
+        group(dxf, 10, point.x);
+        group(dxf, 20, point.y);
+        group(dxf, 30, z);
+        if bulge != 0.0 {
+            group(dxf, 42, bulge);
+        }
+    };
+    let mut at = path.start;
+    for part in &path.parts {
+        let bulge = match *part {
+            Part::Line(_) => 0.0,
+            Part::Arc { sweep, .. } => (sweep / 4.0).tan(),
+        };
+        vertex(at, bulge);
+        at = part.end();
+    }
+    // a closed polyline goes back to its first vertex by itself
+    if !closed {
+        vertex(at, 0.0);
+    }
+    group(dxf, 0, "SEQEND");
+    group(dxf, 8, layer);
+}
+
+/// A DXF of the outlines the model is made from, on a DXF layer for each
+/// iteration, which is at its height in the model. There are `per_iteration`
+/// layers for each iteration, one more than the number added in between.
+pub fn layers_dxf(layers: &[(usize, LayerOutline)], per_iteration: usize) -> String {
+    let mut dxf = String::new();
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "HEADER");
+    group(&mut dxf, 9, "$ACADVER");
+    group(&mut dxf, 1, "AC1009");
+    group(&mut dxf, 0, "ENDSEC");
+
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "TABLES");
+    group(&mut dxf, 0, "TABLE");
+    group(&mut dxf, 2, "LTYPE");
+    group(&mut dxf, 70, 1);
+    group(&mut dxf, 0, "LTYPE");
+    group(&mut dxf, 2, "CONTINUOUS");
+    group(&mut dxf, 70, 0);
+    group(&mut dxf, 3, "Solid line");
+    group(&mut dxf, 72, 65);
+    group(&mut dxf, 73, 0);
+    group(&mut dxf, 40, 0.0);
+    group(&mut dxf, 0, "ENDTAB");
+    group(&mut dxf, 0, "TABLE");
+    group(&mut dxf, 2, "LAYER");
+    group(&mut dxf, 70, layers.len());
+    for (i, (index, _)) in layers.iter().enumerate() {
+        group(&mut dxf, 0, "LAYER");
+        group(&mut dxf, 2, layer_name(*index, per_iteration));
+        group(&mut dxf, 70, 0);
+        group(&mut dxf, 62, COLOURS[i % COLOURS.len()]);
+        group(&mut dxf, 6, "CONTINUOUS");
+    }
+    group(&mut dxf, 0, "ENDTAB");
+    group(&mut dxf, 0, "ENDSEC");
+
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "ENTITIES");
+    for (index, layer) in layers {
+        let name = layer_name(*index, per_iteration);
+        for path in layer.finished() {
+            polyline(&mut dxf, &name, layer.z, path);
+        }
+    }
+    group(&mut dxf, 0, "ENDSEC");
+    group(&mut dxf, 0, "EOF");
+    dxf
+}
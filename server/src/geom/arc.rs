@@ -0,0 +1,656 @@
+use super::line::*;
+use super::point::*;
+use super::threed::Tri3d;
+use crate::simple::curves;
+use std::fmt::*;
+
+/// Part of a circle on a z= plane, going `sweep` radians (anticlockwise if
+/// positive) round `centre` from `start_angle`. It's only split into straight
+/// lines when drawn, with `steps_multiplier` lines per radian. Parts cut from
+/// an arc are drawn with the same lines as the whole of it, so the points on
+/// them are on those lines, and everything joined to the arc meets up.
+#[derive(Clone, Copy, Debug)]
+pub struct Arc3d {
+    centre: Point3d,
+    radius: f64,
+    start_angle: f64,
+    sweep: f64,
+    steps_multiplier: f64,
+    // the start angle and sweep of the arc this was cut from
+    whole: (f64, f64),
+}
+
+impl Arc3d {
+    /// The arc from `start` going `sweep` radians round `centre`.
+    pub fn new(centre: Point3d, start: Point3d, sweep: f64, steps_multiplier: f64) -> Arc3d {
+        let v = start.sub(centre);
+        let start_angle = v.y.atan2(v.x);
+        Arc3d {
+            centre,
+            radius: (v.x * v.x + v.y * v.y).sqrt(),
+            start_angle,
+            sweep,
+            steps_multiplier,
+            whole: (start_angle, sweep),
+        }
+    }
+
+    pub fn start(&self) -> Point3d {
+        self.point(0.0)
+    }
+
+    pub fn end(&self) -> Point3d {
+        self.point(1.0)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.radius * self.sweep.abs()
+    }
+
+    pub fn centre(&self) -> Point3d {
+        self.centre
+    }
+
+    /// How far round the arc goes, in radians (anticlockwise if positive).
+    pub fn sweep(&self) -> f64 {
+        self.sweep
+    }
+
+    /// The point `p` of the way along the arc, as it's drawn.
+    pub fn point(&self, p: f64) -> Point3d {
+        self.offset_point(p, (0.0, 0.0))
+    }
+
+    /// The point `p` of the way along the arc as it's drawn, moved to the right
+    /// (as with lines) by an amount going evenly from `offset.0` at the start of
+    /// the whole arc to `offset.1` at its end, but never past the centre.
+    pub fn offset_point(&self, p: f64, offset: (f64, f64)) -> Point3d {
+        let n = self.whole_steps();
+        let pos = self.grid_position(p);
+        let k = (pos.floor() as i64).max(0).min(n - 1);
+        let t = pos - k as f64;
+        let corner = |k: i64| {
+            let w = k as f64 / n as f64;
+            let (s, c) = (self.whole.0 + self.whole.1 * w).sin_cos();
+            let radius = self.offset_radius(offset.0 + (offset.1 - offset.0) * w).max(0.0);
+            Point3d::new(
+                self.centre.x + radius * c,
+                self.centre.y + radius * s,
+                self.centre.z,
+            )
+        };
+        if t <= 0.0 {
+            corner(k)
+        } else if t >= 1.0 {
+            corner(k + 1)
+        } else {
+            corner(k).add(corner(k + 1).sub(corner(k)).scale(t))
+        }
+    }
+
+    // the right is outwards when going anticlockwise
+    fn offset_radius(&self, offset: f64) -> f64 {
+        self.radius + offset * self.whole.1.signum()
+    }
+
+    // the number of lines the whole arc is drawn with
+    fn whole_steps(&self) -> i64 {
+        ((self.whole.1.abs() * self.steps_multiplier).round() as i64).max(1)
+    }
+
+    // how many of the whole arc's lines along it the point `p` of the way along
+    // this is
+    fn grid_position(&self, p: f64) -> f64 {
+        if self.whole.1 == 0.0 {
+            0.0
+        } else {
+            (self.start_angle + self.sweep * p - self.whole.0) / self.whole.1 * self.whole_steps() as f64
+        }
+    }
+
+    /// The part of the arc between `s` and `e` of the way along it, stopping at
+    /// its ends as lines do.
+    pub fn section(&self, s: f64, e: f64) -> Arc3d {
+        let (s, e) = (s.clamp(0.0, 1.0), e.clamp(0.0, 1.0));
+        Arc3d {
+            start_angle: self.start_angle + self.sweep * s,
+            sweep: self.sweep * (e - s),
+            ..*self
+        }
+    }
+
+    /// The fractions of the way along this that the lines it's drawn with
+    /// change at, not including the ends.
+    pub fn corners(&self) -> Vec<f64> {
+        let (from, to) = (self.grid_position(0.0), self.grid_position(1.0));
+        if (to - from).abs() < 1e-9 {
+            return vec![];
+        }
+        let (low, high) = (from.min(to), from.max(to));
+        let mut corners: Vec<f64> = ((low + 1e-9).ceil() as i64..=(high - 1e-9).floor() as i64)
+            .map(|k| (k as f64 - from) / (to - from))
+            .collect();
+        corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        corners
+    }
+
+    /// The number of straight lines this is drawn with, counted without
+    /// drawing them.
+    pub fn steps(&self) -> i64 {
+        let (from, to) = (self.grid_position(0.0), self.grid_position(1.0));
+        let (low, high) = (from.min(to), from.max(to));
+        if high - low < 1e-9 {
+            1
+        } else {
+            ((high - 1e-9).floor() - (low + 1e-9).ceil()).max(-1.0) as i64 + 2
+        }
+    }
+
+    /// The straight lines this is drawn with.
+    pub fn tessellate(&self) -> Vec<Line3d> {
+        let mut fracs = vec![0.0];
+        fracs.extend(self.corners());
+        fracs.push(1.0);
+        fracs
+            .windows(2)
+            .map(|w| Line3d::new(self.point(w[0]), self.point(w[1])))
+            .collect()
+    }
+}
+
+impl Display for Arc3d {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}->{} round {}", self.start(), self.end(), self.centre)
+    }
+}
+
+/// A part of a layer that is either straight or a circular arc, so curved
+/// layers can be sectioned exactly. Thick layers are curved into
+/// `ThickSegment3d`s instead, which keep their arcs in the same way.
+#[derive(Clone, Copy, Debug)]
+pub enum Segment3d {
+    Line(Line3d),
+    Arc(Arc3d),
+}
+
+impl Segment3d {
+    /// The number of straight lines this is drawn with.
+    pub fn pieces(&self) -> i64 {
+        match self {
+            Segment3d::Line(_) => 1,
+            Segment3d::Arc(arc) => arc.steps(),
+        }
+    }
+
+    /// The straight lines this is drawn with.
+    pub fn tessellate(&self) -> Vec<Line3d> {
+        match self {
+            Segment3d::Line(line) => vec![*line],
+            Segment3d::Arc(arc) => arc.tessellate(),
+        }
+    }
+
+    // where the lines this is drawn with change, as fractions along it
+    fn corners(&self) -> Vec<f64> {
+        match self {
+            Segment3d::Line(_) => vec![],
+            Segment3d::Arc(arc) => arc.corners(),
+        }
+    }
+}
+
+// the fractions from 0 to 1 that either of two things being joined has
+// `corners` at, so they can be joined a straight piece at a time
+fn shared_cuts(a: Vec<f64>, b: Vec<f64>) -> Vec<f64> {
+    let mut cuts = vec![0.0, 1.0];
+    cuts.extend(a);
+    cuts.extend(b);
+    cuts.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    cuts.dedup_by(|x, y| (*x - *y).abs() < 1e-9);
+    cuts
+}
+
+impl Line for Segment3d {
+    fn start(&self) -> Point3d {
+        match self {
+            Segment3d::Line(line) => line.start(),
+            Segment3d::Arc(arc) => arc.start(),
+        }
+    }
+
+    fn end(&self) -> Point3d {
+        match self {
+            Segment3d::Line(line) => line.end(),
+            Segment3d::Arc(arc) => arc.end(),
+        }
+    }
+
+    fn length(&self) -> f64 {
+        match self {
+            Segment3d::Line(line) => line.length(),
+            Segment3d::Arc(arc) => arc.length(),
+        }
+    }
+
+    fn section(&self, s: f64, e: f64) -> Self {
+        match self {
+            Segment3d::Line(line) => Segment3d::Line(line.section(s, e)),
+            Segment3d::Arc(arc) => Segment3d::Arc(arc.section(s, e)),
+        }
+    }
+
+    fn point(&self, p: f64) -> Point3d {
+        match self {
+            Segment3d::Line(line) => line.point(p),
+            Segment3d::Arc(arc) => arc.point(p),
+        }
+    }
+
+    fn direction(&self) -> Point3d {
+        match self {
+            Segment3d::Line(line) => line.direction(),
+            // of the chord, like everything else that treats this as a line
+            Segment3d::Arc(arc) => arc.end().sub(arc.start()).unit(),
+        }
+    }
+
+    fn to2d(&self) -> Line2d {
+        match self {
+            Segment3d::Line(line) => line.to2d(),
+            Segment3d::Arc(arc) => Line2d::new(arc.start().to2d(), arc.end().to2d()),
+        }
+    }
+
+    fn is_parallel_to(&self, other: Self) -> bool {
+        match (self, other) {
+            (Segment3d::Line(a), Segment3d::Line(b)) => a.is_parallel_to(b),
+            _ => false,
+        }
+    }
+
+    // anything other than two lines or two parts of the same circle is replaced
+    // by the straight line between the ends
+    fn merge_with_parallel(&self, other: Self) -> Self {
+        match (self, other) {
+            // only parts of the same arc, so the ends are where they're drawn
+            (Segment3d::Arc(a), Segment3d::Arc(b))
+                if a.centre.sub(b.centre).norm() < EPS
+                    && (a.radius - b.radius).abs() < EPS
+                    && a.whole == b.whole =>
+            {
+                // the angles only match up to whole turns, so go round the
+                // same way as `a` to where `b` ends
+                let turn = 2.0 * std::f64::consts::PI;
+                let to_end = b.start_angle + b.sweep - a.start_angle;
+                let sweep = if a.sweep < 0.0 {
+                    -(-to_end).rem_euclid(turn)
+                } else {
+                    to_end.rem_euclid(turn)
+                };
+                Segment3d::Arc(Arc3d { sweep, ..*a })
+            }
+            _ => Segment3d::Line(Line3d::new(self.start(), other.end())),
+        }
+    }
+
+    fn join_to_with_hole(self, other: Self, frame: f64, reverse: bool) -> Vec<Tri3d> {
+        match (self, other) {
+            (Segment3d::Line(a), Segment3d::Line(b)) => a.join_to_with_hole(b, frame, reverse),
+            _ => self.join_non_parallel(other, 1, None, None, reverse),
+        }
+    }
+
+    fn cuts_with(&self, other: Self) -> Vec<f64> {
+        shared_cuts(self.corners(), other.corners())
+    }
+
+    // cut both wherever either is drawn with a new line, and join the pieces
+    fn join_non_parallel(
+        self,
+        other: Self,
+        steps: i64,
+        hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
+        reverse: bool,
+    ) -> Vec<Tri3d> {
+        let mut tris = vec![];
+        for w in self.cuts_with(other).windows(2) {
+            let piece = |s: Self| {
+                let part = s.section(w[0], w[1]);
+                Line3d::new(part.start(), part.end())
+            };
+            let part_guides = guides.map(|g| g.map(piece));
+            tris.extend(piece(self).join_non_parallel(piece(other), steps, hole_skips, part_guides, reverse));
+        }
+        tris
+    }
+
+    fn draw_layer(_layer: &Vec<Self>, _thickness: f64, _is_top: bool, _cap: CapStyle) -> Vec<Tri3d> {
+        vec![]
+    }
+
+    fn endcap(
+        self,
+        _other: Self,
+        _point: f64,
+        _steps: i64,
+        _hole_skips: Option<(i64, i64)>,
+        _guides: Option<Guides<Self>>,
+        _cap: CapStyle,
+        _reverse: bool,
+    ) -> Vec<Tri3d> {
+        vec![]
+    }
+
+    fn curve(
+        self,
+        prev: Option<Self>,
+        next: Option<Self>,
+        max_curve_frac: f64,
+        steps_multiplier: f64,
+    ) -> Vec<Self> {
+        curves::curve_segment(self, prev, next, max_curve_frac, steps_multiplier)
+    }
+}
+
+impl Display for Segment3d {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Segment3d::Line(line) => write!(f, "{}", line),
+            Segment3d::Arc(arc) => write!(f, "{}", arc),
+        }
+    }
+}
+
+/// An arc thickened by moving it outwards and inwards: `outer` and `inner` to
+/// the right (as with lines) at the start and end of the whole arc, changing
+/// evenly in between.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ThickArc3d {
+    centre: Arc3d,
+    outer: (f64, f64),
+    inner: (f64, f64),
+}
+
+impl ThickArc3d {
+    pub(crate) fn new(centre: Arc3d, outer: (f64, f64), inner: (f64, f64)) -> ThickArc3d {
+        ThickArc3d { centre, outer, inner }
+    }
+
+    // the point on the edge `offsets` away `p` of the way along
+    fn edge_point(&self, offsets: (f64, f64), p: f64) -> Point3d {
+        self.centre.offset_point(p, offsets)
+    }
+
+    // the edge `offsets` away, which is an arc too if it's the same distance
+    // from the centre all the way round
+    fn edge(&self, offsets: (f64, f64)) -> Vec<Segment3d> {
+        let radius = self.centre.offset_radius(offsets.0);
+        if (offsets.0 - offsets.1).abs() < EPS && radius > 0.0 {
+            vec![Segment3d::Arc(Arc3d {
+                radius,
+                ..self.centre
+            })]
+        } else {
+            let mut fracs = vec![0.0];
+            fracs.extend(self.centre.corners());
+            fracs.push(1.0);
+            fracs
+                .windows(2)
+                .map(|w| {
+                    Segment3d::Line(Line3d::new(
+                        self.edge_point(offsets, w[0]),
+                        self.edge_point(offsets, w[1]),
+                    ))
+                })
+                .collect()
+        }
+    }
+
+    // the offsets are for the whole arc, so stay the same
+    fn section(&self, s: f64, e: f64) -> ThickArc3d {
+        ThickArc3d {
+            centre: self.centre.section(s, e),
+            ..*self
+        }
+    }
+
+    // the straight thick line between the ends
+    fn chord(&self) -> ThickLine3d {
+        let edge =
+            |offsets: (f64, f64)| Line3d::new(self.edge_point(offsets, 0.0), self.edge_point(offsets, 1.0));
+        ThickLine3d::new(
+            Line3d::new(self.centre.start(), self.centre.end()),
+            edge(self.outer),
+            edge(self.inner),
+        )
+    }
+}
+
+/// A part of a thickened layer that is either straight or a circular arc, which
+/// like `Segment3d` is only split into lines when drawn.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ThickSegment3d {
+    Line(ThickLine3d),
+    Arc(ThickArc3d),
+}
+
+impl ThickSegment3d {
+    /// The number of straight lines this is drawn with.
+    pub(crate) fn pieces(&self) -> i64 {
+        self.centre().pieces()
+    }
+
+    /// The straight lines this is drawn with.
+    pub(crate) fn tessellate(&self) -> Vec<ThickLine3d> {
+        let mut fracs = vec![0.0];
+        fracs.extend(self.centre().corners());
+        fracs.push(1.0);
+        fracs
+            .windows(2)
+            .map(|w| self.section(w[0], w[1]).chord())
+            .collect()
+    }
+
+    /// The line or arc down the middle.
+    pub(crate) fn centre(&self) -> Segment3d {
+        match self {
+            ThickSegment3d::Line(line) => Segment3d::Line(Line3d::new(line.start(), line.end())),
+            ThickSegment3d::Arc(arc) => Segment3d::Arc(arc.centre),
+        }
+    }
+
+    /// The edge to the right of the centre.
+    pub(crate) fn outer(&self) -> Vec<Segment3d> {
+        match self {
+            ThickSegment3d::Line(line) => vec![Segment3d::Line(line.outer())],
+            ThickSegment3d::Arc(arc) => arc.edge(arc.outer),
+        }
+    }
+
+    /// The edge to the left of the centre.
+    pub(crate) fn inner(&self) -> Vec<Segment3d> {
+        match self {
+            ThickSegment3d::Line(line) => vec![Segment3d::Line(line.inner())],
+            ThickSegment3d::Arc(arc) => arc.edge(arc.inner),
+        }
+    }
+
+    fn chord(&self) -> ThickLine3d {
+        match self {
+            ThickSegment3d::Line(line) => *line,
+            ThickSegment3d::Arc(arc) => arc.chord(),
+        }
+    }
+
+    // the first or last of the lines this is drawn with
+    fn end_piece(&self, point: f64) -> ThickLine3d {
+        let corners = self.centre().corners();
+        if point < 0.5 {
+            self.section(0.0, corners.first().copied().unwrap_or(1.0)).chord()
+        } else {
+            self.section(corners.last().copied().unwrap_or(0.0), 1.0).chord()
+        }
+    }
+}
+
+impl Line for ThickSegment3d {
+    fn start(&self) -> Point3d {
+        self.centre().start()
+    }
+
+    fn end(&self) -> Point3d {
+        self.centre().end()
+    }
+
+    fn length(&self) -> f64 {
+        self.centre().length()
+    }
+
+    fn section(&self, s: f64, e: f64) -> Self {
+        match self {
+            ThickSegment3d::Line(line) => ThickSegment3d::Line(line.section(s, e)),
+            ThickSegment3d::Arc(arc) => ThickSegment3d::Arc(arc.section(s, e)),
+        }
+    }
+
+    fn point(&self, p: f64) -> Point3d {
+        self.centre().point(p)
+    }
+
+    fn direction(&self) -> Point3d {
+        self.centre().direction()
+    }
+
+    fn to2d(&self) -> Line2d {
+        self.centre().to2d()
+    }
+
+    fn is_parallel_to(&self, other: Self) -> bool {
+        match (self, other) {
+            (ThickSegment3d::Line(a), ThickSegment3d::Line(b)) => a.is_parallel_to(b),
+            _ => false,
+        }
+    }
+
+    // as with `Segment3d`, anything other than two lines or two parts of the
+    // same circle is replaced by the straight line between the ends
+    fn merge_with_parallel(&self, other: Self) -> Self {
+        match (self, other) {
+            (ThickSegment3d::Line(a), ThickSegment3d::Line(b)) => {
+                ThickSegment3d::Line(a.merge_with_parallel(b))
+            }
+            (ThickSegment3d::Arc(a), ThickSegment3d::Arc(b)) if a.outer == b.outer && a.inner == b.inner => {
+                match Segment3d::Arc(a.centre).merge_with_parallel(Segment3d::Arc(b.centre)) {
+                    Segment3d::Arc(centre) => ThickSegment3d::Arc(ThickArc3d { centre, ..*a }),
+                    Segment3d::Line(_) => ThickSegment3d::Line(a.chord().merge_with_parallel(b.chord())),
+                }
+            }
+            _ => ThickSegment3d::Line(self.chord().merge_with_parallel(other.chord())),
+        }
+    }
+
+    fn join_to_with_hole(self, other: Self, frame: f64, reverse: bool) -> Vec<Tri3d> {
+        match (self, other) {
+            (ThickSegment3d::Line(a), ThickSegment3d::Line(b)) => a.join_to_with_hole(b, frame, reverse),
+            _ => self.join_non_parallel(other, 1, None, None, reverse),
+        }
+    }
+
+    fn cuts_with(&self, other: Self) -> Vec<f64> {
+        shared_cuts(self.centre().corners(), other.centre().corners())
+    }
+
+    // as with `Segment3d`
+    fn join_non_parallel(
+        self,
+        other: Self,
+        steps: i64,
+        hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
+        reverse: bool,
+    ) -> Vec<Tri3d> {
+        let mut tris = vec![];
+        for w in self.cuts_with(other).windows(2) {
+            let piece = |s: Self| s.section(w[0], w[1]).chord();
+            let part_guides = guides.map(|g| g.map(piece));
+            tris.extend(piece(self).join_non_parallel(piece(other), steps, hole_skips, part_guides, reverse));
+        }
+        tris
+    }
+
+    fn draw_layer(layer: &Vec<Self>, thickness: f64, is_top: bool, cap: CapStyle) -> Vec<Tri3d> {
+        let lines: Vec<ThickLine3d> = layer.iter().flat_map(ThickSegment3d::tessellate).collect();
+        ThickLine3d::draw_layer(&lines, thickness, is_top, cap)
+    }
+
+    fn endcap(
+        self,
+        other: Self,
+        point: f64,
+        steps: i64,
+        hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
+        cap: CapStyle,
+        reverse: bool,
+    ) -> Vec<Tri3d> {
+        if hole_skips.is_some() {
+            // the side of a hole, exactly where the parts either side of it end
+            let at = |s: Self| s.section(point, point).chord();
+            at(self).endcap(
+                at(other),
+                0.0,
+                steps,
+                hole_skips,
+                guides.map(|g| g.map(at)),
+                cap,
+                reverse,
+            )
+        } else {
+            // the end of the layer, shaped the same as the top and bottom are
+            let at = |s: Self| s.end_piece(point);
+            at(self).endcap(
+                at(other),
+                point,
+                steps,
+                None,
+                guides.map(|g| g.map(at)),
+                cap,
+                reverse,
+            )
+        }
+    }
+
+    fn curve(
+        self,
+        prev: Option<Self>,
+        next: Option<Self>,
+        max_curve_frac: f64,
+        steps_multiplier: f64,
+    ) -> Vec<Self> {
+        // arcs are already curved, so are left alone, and don't affect their
+        // neighbours
+        let as_line = |s: Self| match s {
+            ThickSegment3d::Line(line) => Some(line),
+            ThickSegment3d::Arc(_) => None,
+        };
+        match self {
+            ThickSegment3d::Line(line) => line.curve_segments(
+                prev.and_then(as_line),
+                next.and_then(as_line),
+                max_curve_frac,
+                steps_multiplier,
+            ),
+            ThickSegment3d::Arc(_) => vec![self],
+        }
+    }
+}
+
+impl Display for ThickSegment3d {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ThickSegment3d::Line(line) => write!(f, "{}", line),
+            ThickSegment3d::Arc(arc) => write!(f, "{} by {:?} and {:?}", arc.centre, arc.outer, arc.inner),
+        }
+    }
+}
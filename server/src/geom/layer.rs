@@ -1,7 +1,13 @@
 use super::line::*;
+use super::offset::{offset_layer, JoinStyle};
+use super::point::Point3d;
 use std::fmt::{Debug, Display};
 
-#[derive(Debug)]
+// how far (as a fraction of the layer) a line has to overlap a section to be part
+// of it, so rounding doesn't add slivers at the ends
+const SECTION_EPS: f64 = 1e-9;
+
+#[derive(Clone, Debug)]
 pub struct Layer<T>
 where
     T: Line + Copy + Debug + Display,
@@ -41,53 +47,134 @@ where
         self.lines[self.count() - 1]
     }
 
+    /// The fractions of the way along the layer that each line starts at, plus 1.0
+    /// for the end of the last.
+    pub fn fractions(&self) -> Vec<f64> {
+        self.fracs.iter().map(|f| f / self.length).collect()
+    }
+
+    /// The point `frac` of the way along the layer.
+    pub fn point(&self, frac: f64) -> Point3d {
+        if self.count() == 0 {
+            panic!("Cannot find a point on an empty layer");
+        }
+        let pos = frac * self.length;
+        let i = (0..self.count())
+            .find(|&i| pos <= self.fracs[i + 1] && self.lines[i].length() > 0.0)
+            .unwrap_or(self.count() - 1);
+        self.lines[i].point((pos - self.fracs[i]) / (self.fracs[i + 1] - self.fracs[i]))
+    }
+
+    /// The lines between `start` and `end` of the way along the layer, cut down
+    /// to fit. Lines with no length (at bevelled corners) belong to the section
+    /// starting at them, and are never returned on their own for a single point.
     pub fn get_section(&self, start: f64, end: f64) -> Vec<T> {
         return if self.count() == 0 {
             panic!("Cannot get section of empty layer");
         } else {
             let mut out: Vec<T> = Vec::new();
-            for i in 0..self.count() {
-                if end >= self.fracs[i] / self.length && start <= self.fracs[i + 1] / self.length {
-                    let line_frac = (self.fracs[i + 1] - self.fracs[i]) / self.length;
-                    out.push(self.lines[i].section(
-                        (start - self.fracs[i] / self.length) / line_frac,
-                        (end - self.fracs[i] / self.length) / line_frac,
-                    ));
+            if start < end {
+                for i in 0..self.count() {
+                    let line_start = self.fracs[i] / self.length;
+                    let line_end = self.fracs[i + 1] / self.length;
+                    if line_end == line_start {
+                        if line_start >= start - SECTION_EPS && line_start < end - SECTION_EPS {
+                            out.push(self.lines[i]);
+                        }
+                    } else if end - SECTION_EPS > line_start && start + SECTION_EPS < line_end {
+                        let line_frac = line_end - line_start;
+                        out.push(
+                            self.lines[i]
+                                .section((start - line_start) / line_frac, (end - line_start) / line_frac),
+                        );
+                    }
                 }
             }
+            if out.is_empty() {
+                // just a point, on the line that ends there if it's a corner
+                let pos = start.max(0.0).min(1.0) * self.length;
+                let i = (0..self.count())
+                    .find(|&i| {
+                        self.fracs[i + 1] >= pos - SECTION_EPS * self.length && self.lines[i].length() > 0.0
+                    })
+                    .unwrap_or(self.count() - 1);
+                let frac = (pos - self.fracs[i]) / (self.fracs[i + 1] - self.fracs[i]);
+                out.push(self.lines[i].section(frac, frac));
+            }
             out
         };
     }
+
+    /// A single line from the point `start` of the way along the layer to the
+    /// point `end` of the way along, cutting across any corners in between.
+    pub fn chord(&self, start: f64, end: f64) -> T {
+        let section = self.get_section(start, end);
+        section[0].merge_with_parallel(*section.last().unwrap())
+    }
 }
 
 impl Layer<Line3d> {
-    pub(crate) fn thicken(&self, offset: f64) -> Layer<ThickLine3d> {
+    /// The same layer with its lines cut wherever one of `fracs` (of the way
+    /// along the layer) falls inside them.
+    pub(crate) fn split_at(&self, fracs: &[f64]) -> Layer<Line3d> {
+        let mut lines = vec![];
+        for (i, &line) in self.lines.iter().enumerate() {
+            let (line_start, line_end) = (self.fracs[i] / self.length, self.fracs[i + 1] / self.length);
+            let mut cuts: Vec<f64> = fracs
+                .iter()
+                .filter(|&&f| f > line_start + SECTION_EPS && f < line_end - SECTION_EPS)
+                .map(|f| (f - line_start) / (line_end - line_start))
+                .collect();
+            cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            cuts.dedup();
+            cuts.push(1.0);
+            let mut from = 0.0;
+            for cut in cuts {
+                lines.push(line.section(from, cut));
+                from = cut;
+            }
+        }
+        Layer::new(lines)
+    }
+
+    /// Thicken the layer, `thickness(frac)` wide at each point `frac` of the way
+    /// along it.
+    pub(crate) fn thicken<F>(&self, thickness: F, join: JoinStyle) -> Layer<ThickLine3d>
+    where
+        F: Fn(f64) -> f64,
+    {
         let mut lines = vec![];
         // input
         let orig_lines = &self.lines;
+        if orig_lines.len() == 0 {
+            panic!("Failure: cannot thicken an empty layer.");
+        }
+        let widths: Vec<f64> = self.fractions().into_iter().map(|f| thickness(f) / 2.0).collect();
+        let (outers, outer_corners) = offset_layer(orig_lines, &widths, join);
+        let negated: Vec<f64> = widths.iter().map(|w| -w).collect();
+        let (inners, inner_corners) = offset_layer(orig_lines, &negated, join);
 
-        match orig_lines.len() {
-            0 => panic!("Failure: cannot thicken an empty layer."),
-            1 => {
-                lines.push(orig_lines[0].thicken(offset, None, None));
+        for i in 0..orig_lines.len() {
+            lines.push(ThickLine3d::new(orig_lines[i], outers[i], inners[i]));
+            if i + 1 == orig_lines.len() {
+                break;
             }
-            _ => {
-                // sort out first
-                lines.push(orig_lines[0].thicken(offset, None, Some(orig_lines[1])));
-
-                // do middle ones
-                for i in 1..orig_lines.len() - 1 {
-                    lines.push(orig_lines[i].thicken(
-                        offset,
-                        Some(orig_lines[i - 1]),
-                        Some(orig_lines[i + 1]),
-                    ));
-                }
-                // sort out last
-                let last = orig_lines.len() - 1;
-                lines.push(orig_lines[last].thicken(offset, Some(orig_lines[last - 1]), None));
+            // a bevelled or rounded corner goes through more than one point on the
+            // outside, so add lines (with no length) going round it
+            let (outer, inner) = (&outer_corners[i], &inner_corners[i]);
+            let corner = orig_lines[i].end();
+            let part = |points: &Vec<Point3d>, j: usize| match points.len() {
+                1 => Line3d::new(points[0], points[0]),
+                _ => Line3d::new(points[j], points[j + 1]),
+            };
+            for j in 0..outer.len().max(inner.len()) - 1 {
+                lines.push(ThickLine3d::new(
+                    Line3d::new(corner, corner),
+                    part(outer, j),
+                    part(inner, j),
+                ));
             }
-        };
+        }
 
         return Layer::<ThickLine3d>::new(lines);
     }
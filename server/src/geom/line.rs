@@ -1,7 +1,9 @@
+use super::arc::{Segment3d, ThickArc3d, ThickSegment3d};
 use super::point::*;
+use super::rail::Rail;
 use super::threed::{Trapezium3d, Tri3d};
 use crate::simple::curves;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::f64::consts;
 use std::fmt::*;
 
@@ -23,31 +25,43 @@ pub trait Line: Debug + Sized + Display {
 
     // draw a complete surface between two not necessarily parallel lines
     fn join_to(self, other: Self, steps: i64) -> Vec<Tri3d> {
-        self.join_non_parallel(other, steps, None, false)
+        self.join_non_parallel(other, steps, None, None, false)
     }
     // join two parallel lines with a hole in the middle
     fn join_to_with_hole(self, other: Self, frame: f64, reverse: bool) -> Vec<Tri3d>;
-    // join two non-parallel lines, but skipping a bit in the middle of it
+    // join two non-parallel lines, but skipping a bit in the middle of it. If
+    // guides are given the surface curves smoothly through them, otherwise it is
+    // ruled.
     fn join_non_parallel(
         self,
         other: Self,
         steps: i64,
         hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
         reverse: bool,
     ) -> Vec<Tri3d>;
 
+    // the fractions from 0 to 1 along both this and `other` that joining them
+    // has to be cut at, so each piece between them is joined flat
+    fn cuts_with(&self, _other: Self) -> Vec<f64> {
+        vec![0.0, 1.0]
+    }
+
     // shouldn't really be here, but a method to draw out the entire layer with a
-    // certain thickness
-    fn draw_layer(layer: &Vec<Self>, thickness: f64, is_top: bool) -> Vec<Tri3d>;
+    // certain thickness, with ends shaped like `cap`
+    fn draw_layer(layer: &Vec<Self>, thickness: f64, is_top: bool, cap: CapStyle) -> Vec<Tri3d>;
 
     // for lines with thickness, draw an endcap at point/1.0 along the line, joining
-    // it to other.
+    // it to other. The cap style is only used for the ends of a layer (i.e. where
+    // there are no hole skips).
     fn endcap(
         self,
         other: Self,
         point: f64,
         steps: i64,
         hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
+        cap: CapStyle,
         reverse: bool,
     ) -> Vec<Tri3d>;
 
@@ -60,6 +74,63 @@ pub trait Line: Debug + Sized + Display {
     ) -> Vec<Self>;
 }
 
+/// The lines on the layers either side of a join between `a` and `b`:
+/// `before` is beyond `a`, and `after` is beyond `b`. Either is missing at the
+/// first or last layer.
+#[derive(Clone, Copy, Debug)]
+pub struct Guides<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+impl<T> Guides<T>
+where
+    T: Copy,
+{
+    pub fn map<U, F>(&self, f: F) -> Guides<U>
+    where
+        F: Fn(T) -> U,
+    {
+        Guides {
+            before: self.before.map(&f),
+            after: self.after.map(&f),
+        }
+    }
+
+    // for joining b to a instead
+    pub fn flipped(&self) -> Guides<T> {
+        Guides {
+            before: self.after,
+            after: self.before,
+        }
+    }
+}
+
+/// The shape of the ends of a thickened layer.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum CapStyle {
+    /// cut off square, exactly at the end of the line
+    Flat,
+    /// a semicircle, drawn with `steps` straight lines
+    Round { steps: i64 },
+    /// sticks out `size` times the thickness past the end of the line, with the
+    /// corners cut off at 45°, which is up to 0.5 so they don't cross
+    Chamfer { size: f64 },
+}
+
+impl Default for CapStyle {
+    fn default() -> Self {
+        CapStyle::Flat
+    }
+}
+
+/// The rail from `a` to `b`, following the guide points if there are any
+pub(super) fn rail_between(a: Point3d, b: Point3d, guides: Option<Guides<Point3d>>) -> Rail {
+    match guides {
+        None => Rail::straight(a, b),
+        Some(g) => Rail::smooth(g.before, a, b, g.after),
+    }
+}
 
 #[derive(Clone, Copy, Deserialize)]
 pub struct Line3d {
@@ -153,33 +224,16 @@ impl Line for Line3d {
         b: Self,
         steps: i64,
         hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
         reverse: bool,
     ) -> Vec<Tri3d> {
         let a = self;
-        let mut tris = Vec::new();
-        let starts = Line3d::new(a.start, b.start);
-        let ends = Line3d::new(a.end, b.end);
-
-        if hole_skips.is_some() {
-            let (f, u) = hole_skips.unwrap();
-            if f < 1 || u > steps - 1 || u <= f {
-                panic!("Step ranges malformed: f {}, u {}, steps {}", f, u, steps)
-            }
-        }
-        let mut prev = a;
-        for i in 1..=steps {
-            let adj_start = starts.point(i as f64 / steps as f64);
-            let adj_end = ends.point(i as f64 / steps as f64);
-            let new_line = Line3d::new(adj_start, adj_end);
-            if hole_skips.is_none() || (i <= hole_skips.unwrap().0 || i > hole_skips.unwrap().1) {
-                tris.extend_from_slice(&join_planar_lines(prev, new_line, reverse));
-            }
-            prev = new_line;
-        }
-        tris
+        let starts = rail_between(a.start, b.start, guides.map(|g| g.map(|l| l.start)));
+        let ends = rail_between(a.end, b.end, guides.map(|g| g.map(|l| l.end)));
+        join_rails(starts, ends, steps, hole_skips, reverse)
     }
 
-    fn draw_layer(_layer: &Vec<Self>, _thickness: f64, _is_top: bool) -> Vec<Tri3d> {
+    fn draw_layer(_layer: &Vec<Self>, _thickness: f64, _is_top: bool, _cap: CapStyle) -> Vec<Tri3d> {
         vec![]
     }
 
@@ -189,6 +243,8 @@ impl Line for Line3d {
         _point: f64,
         _steps: i64,
         _hole_skips: Option<(i64, i64)>,
+        _guides: Option<Guides<Self>>,
+        _cap: CapStyle,
         _reverse: bool,
     ) -> Vec<Tri3d> {
         vec![]
@@ -204,6 +260,37 @@ impl Line for Line3d {
     }
 }
 
+/// Draw the surface swept out by a line from `starts.point(0)` ->
+/// `ends.point(0)` to `starts.point(1)` -> `ends.point(1)`, skipping the steps
+/// in `hole_skips` if given.
+pub(super) fn join_rails(
+    starts: Rail,
+    ends: Rail,
+    steps: i64,
+    hole_skips: Option<(i64, i64)>,
+    reverse: bool,
+) -> Vec<Tri3d> {
+    let mut tris = Vec::new();
+
+    if hole_skips.is_some() {
+        let (f, u) = hole_skips.unwrap();
+        if f < 1 || u > steps - 1 || u <= f {
+            panic!("Step ranges malformed: f {}, u {}, steps {}", f, u, steps)
+        }
+    }
+    let mut prev = Line3d::new(starts.point(0.0), ends.point(0.0));
+    for i in 1..=steps {
+        let adj_start = starts.point(i as f64 / steps as f64);
+        let adj_end = ends.point(i as f64 / steps as f64);
+        let new_line = Line3d::new(adj_start, adj_end);
+        if hole_skips.is_none() || (i <= hole_skips.unwrap().0 || i > hole_skips.unwrap().1) {
+            tris.extend_from_slice(&join_planar_lines(prev, new_line, reverse));
+        }
+        prev = new_line;
+    }
+    tris
+}
+
 pub(super) fn join_planar_lines(a: Line3d, b: Line3d, reverse: bool) -> [Tri3d; 2] {
     return [
         Tri3d::from_sp(&a, &b.start, reverse),
@@ -236,6 +323,156 @@ impl ThickLine3d {
             inner,
         }
     }
+
+    /// The edge to the right of the centre.
+    pub(crate) fn outer(&self) -> Line3d {
+        self.outer
+    }
+
+    /// The edge to the left of the centre.
+    pub(crate) fn inner(&self) -> Line3d {
+        self.inner
+    }
+
+    // how far `point` is to the right of the line through the centre of this
+    fn side_offset(&self, point: Point3d) -> f64 {
+        let dir = self.original.direction();
+        let v = point.sub(self.original.start);
+        v.x * dir.y - v.y * dir.x
+    }
+
+    /// The outline of the end of the line at `point` (0.0 or 1.0), going from the
+    /// inner edge to the outer one.
+    pub(crate) fn end_profile(&self, point: f64, cap: CapStyle) -> Vec<Point3d> {
+        let inner = self.inner.point(point);
+        let outer = self.outer.point(point);
+        // pointing away from the line
+        let out = self
+            .original
+            .direction()
+            .scale(if point < 0.5 { -1.0 } else { 1.0 });
+        let width = outer.sub(inner).norm();
+        match cap {
+            CapStyle::Flat => vec![inner, outer],
+            CapStyle::Round { steps } => {
+                let centre = inner.add(outer).scale(0.5);
+                let radial = inner.sub(centre);
+                let steps = steps.max(1);
+                (0..=steps)
+                    .map(|k| {
+                        let (s, c) = (consts::PI * k as f64 / steps as f64).sin_cos();
+                        centre.add(radial.scale(c)).add(out.scale(s * width / 2.0))
+                    })
+                    .collect()
+            }
+            CapStyle::Chamfer { size } => {
+                let across = outer.sub(inner).unit();
+                let e = (size * width).min(width / 2.0);
+                vec![
+                    inner,
+                    inner.add(out.scale(e)).add(across.scale(e)),
+                    outer.add(out.scale(e)).sub(across.scale(e)),
+                    outer,
+                ]
+            }
+        }
+    }
+
+    /// Curves the ends of the line to meet `prev` and `next`, like
+    /// `curves::fillet_line`, keeping the curves as arcs.
+    pub(super) fn curve_segments(
+        self,
+        prev: Option<ThickLine3d>,
+        next: Option<ThickLine3d>,
+        max_curve_frac: f64,
+        steps_multiplier: f64,
+    ) -> Vec<ThickSegment3d> {
+        // the pieces round bevelled and rounded corners are replaced by the curve
+        // if there is one, and otherwise left as they are
+        if self.length() == 0.0 {
+            return match (prev, next) {
+                (Some(prev), Some(next))
+                    if curves::is_filleted(prev.original, next.original, max_curve_frac) =>
+                {
+                    vec![]
+                }
+                _ => vec![ThickSegment3d::Line(self)],
+            };
+        }
+        // curve the centre, then move that in and out for the edges
+        let centre = curves::fillet_line(
+            self.original,
+            prev.map(|l| l.original),
+            next.map(|l| l.original),
+            max_curve_frac,
+            steps_multiplier,
+        );
+        let outer = (
+            self.side_offset(self.outer.start),
+            self.side_offset(self.outer.end),
+        );
+        let inner = (
+            self.side_offset(self.inner.start),
+            self.side_offset(self.inner.end),
+        );
+        let total: f64 = centre.iter().map(|s| s.length()).sum();
+
+        // ends that weren't curved keep their corners as they were
+        let keep_start = centre[0].start().sub(self.original.start).norm() < EPS;
+        let keep_end = centre[centre.len() - 1].end().sub(self.original.end).norm() < EPS;
+
+        let mut segments = vec![];
+        let mut along = 0.0;
+        for segment in centre {
+            // the edges taper from one end of the whole line to the other
+            let (s, e) = (along / total, (along + segment.length()) / total);
+            let at = |(start, end): (f64, f64), frac: f64| start + (end - start) * frac;
+            segments.push(match segment {
+                Segment3d::Line(line) => {
+                    let dir = line.direction();
+                    let right = Point3d::new(dir.y, -dir.x, 0.0);
+                    let edge = |offsets: (f64, f64)| {
+                        Line3d::new(
+                            line.start.add(right.scale(at(offsets, s))),
+                            line.end.add(right.scale(at(offsets, e))),
+                        )
+                    };
+                    ThickSegment3d::Line(ThickLine3d::new(line, edge(outer), edge(inner)))
+                }
+                Segment3d::Arc(arc) => ThickSegment3d::Arc(ThickArc3d::new(
+                    arc,
+                    (at(outer, s), at(outer, e)),
+                    (at(inner, s), at(inner, e)),
+                )),
+            });
+            along += segment.length();
+        }
+        if let (true, ThickSegment3d::Line(first)) = (keep_start, segments[0]) {
+            segments[0] = ThickSegment3d::Line(ThickLine3d::new(
+                first.original,
+                Line3d::new(self.outer.start, first.outer.end),
+                Line3d::new(self.inner.start, first.inner.end),
+            ));
+        }
+        let n = segments.len() - 1;
+        if let (true, ThickSegment3d::Line(last)) = (keep_end, segments[n]) {
+            segments[n] = ThickSegment3d::Line(ThickLine3d::new(
+                last.original,
+                Line3d::new(last.outer.start, self.outer.end),
+                Line3d::new(last.inner.start, self.inner.end),
+            ));
+        }
+        segments
+    }
+}
+
+// flips tri if needed so it faces up (or down)
+fn facing_vertically(tri: Tri3d, up: bool) -> Tri3d {
+    if (tri.n.z > 0.0) == up {
+        tri
+    } else {
+        Tri3d::new(tri.a, tri.c, tri.b, tri.n.scale(-1.0))
+    }
 }
 
 impl Line for ThickLine3d {
@@ -275,6 +512,13 @@ impl Line for ThickLine3d {
         self.original.to2d()
     }
 
+    // the edges have to be parallel too, or the holes cut between them won't be flat
+    fn is_parallel_to(&self, other: Self) -> bool {
+        self.original.is_parallel_to(other.original)
+            && self.outer.is_parallel_to(other.outer)
+            && self.inner.is_parallel_to(other.inner)
+    }
+
     fn merge_with_parallel(&self, other: Self) -> Self {
         ThickLine3d::new(
             self.original.merge_with_parallel(other.original),
@@ -329,17 +573,20 @@ impl Line for ThickLine3d {
         other: Self,
         steps: i64,
         hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
         reverse: bool,
     ) -> Vec<Tri3d> {
         let mut tris = vec![];
+        let inner_guides = guides.map(|g| g.map(|l| l.inner));
+        let outer_guides = guides.map(|g| g.map(|l| l.outer));
 
         tris.extend(
             self.inner
-                .join_non_parallel(other.inner, steps, hole_skips, !reverse),
+                .join_non_parallel(other.inner, steps, hole_skips, inner_guides, !reverse),
         );
         tris.extend(
             self.outer
-                .join_non_parallel(other.outer, steps, hole_skips, reverse),
+                .join_non_parallel(other.outer, steps, hole_skips, outer_guides, reverse),
         );
 
         if hole_skips.is_some() {
@@ -349,10 +596,30 @@ impl Line for ThickLine3d {
 
             let bot = (until) as f64 / steps as f64;
             // joins to make solid
-            let outer_starts = Line3d::new(other.outer.start, self.outer.start);
-            let inner_starts = Line3d::new(other.inner.start, self.inner.start);
-            let outer_ends = Line3d::new(other.outer.end, self.outer.end);
-            let inner_ends = Line3d::new(other.inner.end, self.inner.end);
+            let outer_starts = rail_between(
+                self.outer.start,
+                other.outer.start,
+                outer_guides.map(|g| g.map(|l| l.start)),
+            )
+            .reversed();
+            let inner_starts = rail_between(
+                self.inner.start,
+                other.inner.start,
+                inner_guides.map(|g| g.map(|l| l.start)),
+            )
+            .reversed();
+            let outer_ends = rail_between(
+                self.outer.end,
+                other.outer.end,
+                outer_guides.map(|g| g.map(|l| l.end)),
+            )
+            .reversed();
+            let inner_ends = rail_between(
+                self.inner.end,
+                other.inner.end,
+                inner_guides.map(|g| g.map(|l| l.end)),
+            )
+            .reversed();
             // Top hole inside
             tris.extend_from_slice(&join_planar_lines(
                 Line3d::new(outer_starts.point(top), outer_ends.point(top)),
@@ -370,7 +637,7 @@ impl Line for ThickLine3d {
     }
     // draw the layer in it's entirety, thickened by thickness (a positive number)
     // either upwards (is_top = true) or downwards.
-    fn draw_layer(layer: &Vec<Self>, thickness: f64, is_top: bool) -> Vec<Tri3d> {
+    fn draw_layer(layer: &Vec<Self>, thickness: f64, is_top: bool, cap: CapStyle) -> Vec<Tri3d> {
         let mut tris = vec![];
 
         let adjust = Point3d::new(0.0, 0.0, if is_top { 1.0 } else { -1.0 } * thickness);
@@ -394,20 +661,33 @@ impl Line for ThickLine3d {
         };
         // if thickening vertically, add endcaps at both ends
         if thickness.abs() >= 1e-7 {
-            tris.extend(layer[0].endcap(adjusted[0], 0.0, 1, None, !is_top));
+            tris.extend(layer[0].endcap(adjusted[0], 0.0, 1, None, None, cap, !is_top));
 
-            tris.extend(
-                layer
-                    .last()
-                    .unwrap()
-                    .endcap(*adjusted.last().unwrap(), 1.0, 1, None, is_top),
-            );
+            tris.extend(layer.last().unwrap().endcap(
+                *adjusted.last().unwrap(),
+                1.0,
+                1,
+                None,
+                None,
+                cap,
+                is_top,
+            ));
         }
         // draw very top and very bottom
         for i in 0..adjusted.len() {
             tris.extend_from_slice(&join_planar_lines(adjusted[i].inner, adjusted[i].outer, is_top));
             if thickness > 1e-7 {
-                tris.extend(adjusted[i].join_non_parallel(layer[i], 1, None, is_top));
+                tris.extend(adjusted[i].join_non_parallel(layer[i], 1, None, None, is_top));
+            }
+        }
+        // and fill in the ends (there is nothing to do if they are flat)
+        for (line, point) in [(adjusted[0], 0.0), (*adjusted.last().unwrap(), 1.0)].iter() {
+            let profile = line.end_profile(*point, cap);
+            for k in 2..profile.len() {
+                tris.push(facing_vertically(
+                    Tri3d::from_sp(&Line3d::new(profile[0], profile[k - 1]), &profile[k], false),
+                    is_top,
+                ));
             }
         }
         return tris;
@@ -418,25 +698,44 @@ impl Line for ThickLine3d {
         point: f64,
         steps: i64,
         hole_skips: Option<(i64, i64)>,
+        guides: Option<Guides<Self>>,
+        cap: CapStyle,
         reverse: bool,
     ) -> Vec<Tri3d> {
-       
         return if hole_skips.is_none() {
-            Line3d::new(self.inner.point(point), self.outer.point(point)).join_non_parallel(
-                Line3d::new(other.inner.point(point), other.outer.point(point)),
-                steps,
-                hole_skips,
-                reverse,
-            )
+            // join each part of the outline of the end to the same part on other
+            let profile = self.end_profile(point, cap);
+            let other_profile = other.end_profile(point, cap);
+            let rail = |k: usize| {
+                rail_between(
+                    profile[k],
+                    other_profile[k],
+                    guides.map(|g| g.map(|l| l.end_profile(point, cap)[k])),
+                )
+            };
+            let mut tris = vec![];
+            for k in 1..profile.len() {
+                tris.extend(join_rails(rail(k - 1), rail(k), steps, None, reverse));
+            }
+            tris
         } else {
-            let inners = Line3d::new(self.inner.point(point), other.inner.point(point));
-            let outers = Line3d::new(self.outer.point(point), other.outer.point(point));
+            let inners = rail_between(
+                self.inner.point(point),
+                other.inner.point(point),
+                guides.map(|g| g.map(|l| l.inner.point(point))),
+            );
+            let outers = rail_between(
+                self.outer.point(point),
+                other.outer.point(point),
+                guides.map(|g| g.map(|l| l.outer.point(point))),
+            );
             let skips = hole_skips.unwrap();
             let top = skips.0 as f64 / steps as f64;
 
             let bottom = skips.1 as f64 / steps as f64;
-            Line3d::new(inners.point(top), outers.point(top)).join_non_parallel(
-                Line3d::new(inners.point(bottom), outers.point(bottom)),
+            join_rails(
+                inners.section(top, bottom),
+                outers.section(top, bottom),
                 skips.1 - skips.0,
                 None,
                 !reverse,
@@ -444,8 +743,6 @@ impl Line for ThickLine3d {
         };
     }
 
-
-    
     fn curve(
         self,
         prev: Option<ThickLine3d>,
@@ -453,37 +750,10 @@ impl Line for ThickLine3d {
         max_curve_frac: f64,
         steps_multiplier: f64,
     ) -> Vec<ThickLine3d> {
-        let outers = curves::curve_line(
-            self.outer,
-            prev.map(|l| l.outer),
-            next.map(|l| l.outer),
-            max_curve_frac,
-            steps_multiplier,
-        );
-
-        let origis = curves::curve_line(
-            self.original,
-            prev.map(|l| l.original),
-            next.map(|l| l.original),
-            max_curve_frac,
-            steps_multiplier,
-        );
-
-        let inners = curves::curve_line(
-            self.inner,
-            prev.map(|l| l.inner),
-            next.map(|l| l.inner),
-            max_curve_frac,
-            steps_multiplier,
-        );
-        if outers.len() != origis.len() || origis.len() != inners.len() {
-            panic!("Wrong lengths!!!!");
-        }
-        let mut lines = vec![];
-        for i in 0..outers.len() {
-            lines.push(ThickLine3d::new(origis[i], outers[i], inners[i]))
-        }
-        lines
+        self.curve_segments(prev, next, max_curve_frac, steps_multiplier)
+            .iter()
+            .flat_map(ThickSegment3d::tessellate)
+            .collect()
     }
 }
 impl Display for ThickLine3d {
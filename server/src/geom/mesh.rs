@@ -0,0 +1,393 @@
+//! Operations on whole meshes (i.e. lists of triangles), rather than the
+//! lines they were made from.
+
+use super::line::Line3d;
+use super::point::*;
+use super::threed::Tri3d;
+use super::twod::polygon_area;
+use std::collections::HashMap;
+
+/// Somewhere to put triangles as they're made, so a whole mesh doesn't have
+/// to be kept around if it's going straight into a file.
+pub trait TriSink {
+    fn push(&mut self, tri: Tri3d);
+
+    /// How many triangles have been pushed so far.
+    fn count(&self) -> usize;
+
+    fn push_all(&mut self, tris: Vec<Tri3d>) {
+        for tri in tris {
+            self.push(tri);
+        }
+    }
+}
+
+impl TriSink for Vec<Tri3d> {
+    fn push(&mut self, tri: Tri3d) {
+        Vec::push(self, tri);
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn push_all(&mut self, tris: Vec<Tri3d>) {
+        self.extend(tris);
+    }
+}
+
+type VertexKey = (i64, i64, i64);
+
+fn vertex_key(point: Point3d, tolerance: f64) -> VertexKey {
+    (
+        (point.x / tolerance).round() as i64,
+        (point.y / tolerance).round() as i64,
+        (point.z / tolerance).round() as i64,
+    )
+}
+
+/// Joins up a mesh made of several pieces that touch:
+///     - vertices closer than `tolerance` are merged
+///     - triangles that have collapsed to a line or point are removed
+///     - pairs of identical triangles facing opposite ways are removed, as they
+///       are where two pieces were glued together, and so inside the solid
+///     - duplicated triangles facing the same way are only kept once
+///     - where faces facing opposite ways overlap but are split into different
+///       triangles, the overlap is cut out of both
+///     - triangles with another's corner part way along a side are split there,
+///       so every side is shared by exactly two triangles
+pub fn weld(tris: Vec<Tri3d>, tolerance: f64) -> Vec<Tri3d> {
+    let tris = cancel(tris, tolerance);
+    let tris = cancel(cut_overlaps(tris, tolerance), tolerance);
+    split_sides(tris, tolerance)
+}
+
+// merges close vertices, and cancels out triangles with the same corners
+fn cancel(tris: Vec<Tri3d>, tolerance: f64) -> Vec<Tri3d> {
+    // the first point seen in each cell stands in for all the others
+    let mut vertices: HashMap<VertexKey, Point3d> = HashMap::new();
+    let mut snap = |p: Point3d| -> (VertexKey, Point3d) {
+        let key = vertex_key(p, tolerance);
+        (key, *vertices.entry(key).or_insert(p))
+    };
+
+    let mut welded = vec![];
+    // for each set of three vertices, the triangles using them with each winding
+    let mut faces: HashMap<[VertexKey; 3], (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for tri in tris {
+        let (ka, a) = snap(tri.a);
+        let (kb, b) = snap(tri.b);
+        let (kc, c) = snap(tri.c);
+        if ka == kb || kb == kc || ka == kc {
+            continue;
+        }
+        // rotate so the smallest key is first, which means two triangles with
+        // the same winding get the same key, and opposite windings differ only
+        // in the order of the last two
+        let keys = [ka, kb, kc];
+        let first = (0..3).min_by_key(|&i| keys[i]).unwrap();
+        let (k1, k2) = (keys[(first + 1) % 3], keys[(first + 2) % 3]);
+        let (face, forwards) = if k1 < k2 {
+            ([keys[first], k1, k2], true)
+        } else {
+            ([keys[first], k2, k1], false)
+        };
+        let entry = faces.entry(face).or_insert((vec![], vec![]));
+        if forwards { &mut entry.0 } else { &mut entry.1 }.push(welded.len());
+        welded.push(Tri3d::from_sp(&Line3d::new(a, b), &c, false));
+    }
+
+    let mut keep = vec![false; welded.len()];
+    for (forwards, backwards) in faces.values() {
+        // opposite faces cancel out, and of whatever is left only one is needed
+        if forwards.len() > backwards.len() {
+            keep[forwards[0]] = true;
+        } else if backwards.len() > forwards.len() {
+            keep[backwards[0]] = true;
+        }
+    }
+    welded
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, k)| *k)
+        .map(|(t, _)| t)
+        .collect()
+}
+
+fn unit_normal(tri: &Tri3d) -> Option<Point3d> {
+    let normal = tri.b.sub(tri.a).cross(tri.c.sub(tri.a));
+    if normal.norm() > 0.0 {
+        Some(normal.unit())
+    } else {
+        None
+    }
+}
+
+// roughly which plane a triangle is in, whichever way it faces, so that
+// triangles in the same plane (almost always) get the same key
+fn plane_key(normal: Point3d, point: Point3d) -> [i64; 4] {
+    // the first part of the normal that's clearly not 0 is made positive
+    let flip = matches!(
+        [normal.x, normal.y, normal.z].iter().find(|c| c.abs() > 0.1),
+        Some(&c) if c < 0.0
+    );
+    let n = if flip { normal.scale(-1.0) } else { normal };
+    let k = |x: f64| (x * 1e3).round() as i64;
+    [k(n.x), k(n.y), k(n.z), k(n.dot(point))]
+}
+
+fn bounds(tri: &Tri3d) -> (Point3d, Point3d) {
+    let min = |a: f64, b: f64, c: f64| a.min(b).min(c);
+    let max = |a: f64, b: f64, c: f64| a.max(b).max(c);
+    (
+        Point3d::new(
+            min(tri.a.x, tri.b.x, tri.c.x),
+            min(tri.a.y, tri.b.y, tri.c.y),
+            min(tri.a.z, tri.b.z, tri.c.z),
+        ),
+        Point3d::new(
+            max(tri.a.x, tri.b.x, tri.c.x),
+            max(tri.a.y, tri.b.y, tri.c.y),
+            max(tri.a.z, tri.b.z, tri.c.z),
+        ),
+    )
+}
+
+// cuts whatever's covered by a triangle in the same plane facing the other way
+// out of each triangle, which leaves only the parts of either that stick out
+fn cut_overlaps(tris: Vec<Tri3d>, tolerance: f64) -> Vec<Tri3d> {
+    let normals: Vec<Option<Point3d>> = tris.iter().map(unit_normal).collect();
+    let mut planes: HashMap<[i64; 4], Vec<usize>> = HashMap::new();
+    for (i, normal) in normals.iter().enumerate() {
+        if let Some(normal) = normal {
+            planes.entry(plane_key(*normal, tris[i].a)).or_default().push(i);
+        }
+    }
+
+    let boxes: Vec<(Point3d, Point3d)> = tris.iter().map(bounds).collect();
+    let touch = |i: usize, j: usize| {
+        let ((min, max), (other_min, other_max)) = (boxes[i], boxes[j]);
+        other_min.x < max.x + tolerance
+            && other_min.y < max.y + tolerance
+            && other_min.z < max.z + tolerance
+            && min.x < other_max.x + tolerance
+            && min.y < other_max.y + tolerance
+            && min.z < other_max.z + tolerance
+    };
+
+    let mut cut: Vec<Option<Vec<Tri3d>>> = vec![None; tris.len()];
+    for plane in planes.values() {
+        let facing = normals[plane[0]].unwrap();
+        let (ahead, behind): (Vec<usize>, Vec<usize>) = plane
+            .iter()
+            .partition(|&&i| normals[i].unwrap().dot(facing) > 0.0);
+        for (these, others) in &[(&ahead, &behind), (&behind, &ahead)] {
+            for &i in these.iter() {
+                let normal = normals[i].unwrap();
+                let overlapping: Vec<&Tri3d> = others
+                    .iter()
+                    .filter(|&&j| {
+                        touch(i, j)
+                            && [tris[j].a, tris[j].b, tris[j].c]
+                                .iter()
+                                .all(|p| p.sub(tris[i].a).dot(normal).abs() < tolerance)
+                    })
+                    .map(|&j| &tris[j])
+                    .collect();
+                if !overlapping.is_empty() {
+                    cut[i] = cut_out(&tris[i], normal, &overlapping, tolerance);
+                }
+            }
+        }
+    }
+    tris.into_iter()
+        .zip(cut)
+        .flat_map(|(tri, cut)| cut.unwrap_or_else(|| vec![tri]))
+        .collect()
+}
+
+// `tri` without the parts covered by `others`, if any of it is
+fn cut_out(tri: &Tri3d, normal: Point3d, others: &[&Tri3d], tolerance: f64) -> Option<Vec<Tri3d>> {
+    // flat on the triangle, which goes anticlockwise
+    let across = tri.b.sub(tri.a).unit();
+    let up = normal.cross(across);
+    let flat = |p: Point3d| Point2d::new(p.sub(tri.a).dot(across), p.sub(tri.a).dot(up));
+    let unflat = |p: Point2d| tri.a.add(across.scale(p.x)).add(up.scale(p.y));
+
+    let mut pieces = vec![vec![flat(tri.a), flat(tri.b), flat(tri.c)]];
+    let mut changed = false;
+    for other in others {
+        // which faces the other way, so is anticlockwise backwards
+        let other = [flat(other.a), flat(other.c), flat(other.b)];
+        let mut next = vec![];
+        for piece in pieces {
+            match subtract(&piece, &other, tolerance) {
+                Some(left) => {
+                    changed = true;
+                    next.extend(left);
+                }
+                None => next.push(piece),
+            }
+        }
+        pieces = next;
+    }
+    if !changed {
+        return None;
+    }
+    Some(
+        pieces
+            .iter()
+            .flat_map(|piece| fan(&piece.iter().map(|&p| unflat(p)).collect::<Vec<_>>()))
+            .collect(),
+    )
+}
+
+// the convex pieces of `piece` outside the anticlockwise triangle `tri`, or
+// None if they don't overlap
+fn subtract(piece: &[Point2d], tri: &[Point2d; 3], tolerance: f64) -> Option<Vec<Vec<Point2d>>> {
+    let mut inside = piece.to_vec();
+    let mut outside = vec![];
+    for i in 0..3 {
+        let (from, to) = (tri[i], tri[(i + 1) % 3]);
+        let beyond = clip(&inside, to, from);
+        if wide(&beyond, tolerance) {
+            outside.push(beyond);
+        }
+        inside = clip(&inside, from, to);
+        if !wide(&inside, tolerance) {
+            return None;
+        }
+    }
+    Some(outside)
+}
+
+// the part of the convex polygon `points` to the left of the line from `from`
+// to `to`
+fn clip(points: &[Point2d], from: Point2d, to: Point2d) -> Vec<Point2d> {
+    let side = |p: Point2d| (to.x - from.x) * (p.y - from.y) - (to.y - from.y) * (p.x - from.x);
+    let mut out = vec![];
+    for i in 0..points.len() {
+        let (p, q) = (points[i], points[(i + 1) % points.len()]);
+        let (sp, sq) = (side(p), side(q));
+        if sp >= 0.0 {
+            out.push(p);
+        }
+        if (sp > 0.0 && sq < 0.0) || (sp < 0.0 && sq > 0.0) {
+            out.push(p.add(q.sub(p).scale(sp / (sp - sq))));
+        }
+    }
+    out
+}
+
+// whether a polygon is any wider than `tolerance`, rather than a sliver left by
+// rounding errors
+fn wide(points: &[Point2d], tolerance: f64) -> bool {
+    let perimeter: f64 = (0..points.len())
+        .map(|i| points[(i + 1) % points.len()].sub(points[i]).norm())
+        .sum();
+    points.len() >= 3 && polygon_area(points).abs() / 2.0 > tolerance * perimeter
+}
+
+// triangles filling a convex polygon, which may have corners part way along its
+// sides, from a point in the middle
+fn fan(points: &[Point3d]) -> Vec<Tri3d> {
+    let tri = |a: Point3d, b: Point3d, c: Point3d| Tri3d::from_sp(&Line3d::new(a, b), &c, false);
+    if points.len() == 3 {
+        return vec![tri(points[0], points[1], points[2])];
+    }
+    let middle = points
+        .iter()
+        .fold(Point3d::new(0.0, 0.0, 0.0), |sum, p| sum.add(*p))
+        .scale(1.0 / points.len() as f64);
+    (0..points.len())
+        .map(|i| tri(middle, points[i], points[(i + 1) % points.len()]))
+        .collect()
+}
+
+// splits triangles with corners of others part way along their sides
+fn split_sides(tris: Vec<Tri3d>, tolerance: f64) -> Vec<Tri3d> {
+    let key = |p: Point3d| vertex_key(p, tolerance);
+    // how many times each side is used each way round, which is once each for
+    // sides that are joined up properly
+    let mut uses: HashMap<(VertexKey, VertexKey), (usize, usize)> = HashMap::new();
+    for tri in &tris {
+        for &(p, q) in &[(tri.a, tri.b), (tri.b, tri.c), (tri.c, tri.a)] {
+            let (kp, kq) = (key(p), key(q));
+            if kp < kq {
+                uses.entry((kp, kq)).or_default().0 += 1;
+            } else {
+                uses.entry((kq, kp)).or_default().1 += 1;
+            }
+        }
+    }
+    // a corner part way along a side is joined to its ends by other sides that
+    // aren't joined up either, so only those need looking at
+    let mut points: HashMap<VertexKey, Point3d> = HashMap::new();
+    let mut next: HashMap<VertexKey, Vec<VertexKey>> = HashMap::new();
+    for tri in &tris {
+        for &(p, q) in &[(tri.a, tri.b), (tri.b, tri.c), (tri.c, tri.a)] {
+            let (kp, kq) = (key(p), key(q));
+            if uses[&(kp.min(kq), kp.max(kq))] != (1, 1) {
+                points.insert(kp, p);
+                points.insert(kq, q);
+                next.entry(kp).or_default().push(kq);
+                next.entry(kq).or_default().push(kp);
+            }
+        }
+    }
+    if next.is_empty() {
+        return tris;
+    }
+
+    // the corners part way along the side from `p` to `q`, in order, found by
+    // following sides that aren't joined up along it
+    let between = |p: Point3d, q: Point3d| -> Vec<Point3d> {
+        let (kp, kq) = (key(p), key(q));
+        if uses[&(kp.min(kq), kp.max(kq))] == (1, 1) {
+            return vec![];
+        }
+        let (along, length) = (q.sub(p).unit(), q.sub(p).norm());
+        let mut found = vec![];
+        let (mut at, mut t) = (kp, 0.0);
+        loop {
+            // the nearest corner further along the side
+            let step = next[&at]
+                .iter()
+                .filter_map(|k| {
+                    let r = points[k].sub(p);
+                    let s = r.dot(along);
+                    let off = r.sub(along.scale(s)).norm();
+                    if s > t + tolerance && s < length + tolerance && off < tolerance {
+                        Some((s, *k))
+                    } else {
+                        None
+                    }
+                })
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            match step {
+                Some((_, k)) if k == kq => return found,
+                Some((s, k)) => {
+                    found.push(points[&k]);
+                    at = k;
+                    t = s;
+                }
+                None => return vec![],
+            }
+        }
+    };
+
+    let mut out = vec![];
+    for tri in tris {
+        let mut corners = vec![];
+        for &(p, q) in &[(tri.a, tri.b), (tri.b, tri.c), (tri.c, tri.a)] {
+            corners.push(p);
+            corners.extend(between(p, q));
+        }
+        if corners.len() == 3 {
+            out.push(tri);
+        } else {
+            out.extend(fan(&corners));
+        }
+    }
+    out
+}
@@ -1,11 +1,21 @@
+mod arc;
 mod layer;
 mod line;
+mod mesh;
 mod offset;
 mod point;
+mod rail;
 mod threed;
 mod twod;
 
+pub(crate) use self::arc::ThickSegment3d;
+pub use self::arc::{Arc3d, Segment3d};
 pub use self::layer::Layer;
 pub use self::line::*;
+pub use self::mesh::*;
+pub use self::offset::JoinStyle;
+#[cfg(test)]
+pub(crate) use self::offset::{offset_layer, offset_line2};
 pub use self::point::*;
 pub use self::threed::*;
+pub(crate) use self::twod::{convex_hull, triangulate_polygon};
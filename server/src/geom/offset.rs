@@ -9,9 +9,32 @@
 
 use super::line::*;
 use super::point::*;
+use serde::{Deserialize, Serialize};
+
+/// How the offset edges of a thickened layer meet at corners.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum JoinStyle {
+    /// extend the edges until they meet, unless that point is more than `limit`
+    /// times the offset from the corner, in which case bevel it instead
+    Mitre { limit: f64 },
+    /// cut the corner off with a single straight line
+    Bevel,
+    /// go round the corner with an arc of `steps` straight lines
+    Round { steps: i64 },
+}
+
+impl Default for JoinStyle {
+    fn default() -> Self {
+        JoinStyle::Mitre { limit: f64::INFINITY }
+    }
+}
+
+/// How far the inside edge at a corner can be from it (as a multiple of the
+/// offset) when bevelling or rounding, as those only change the outside edge.
+const INSIDE_LIMIT: f64 = 4.0;
 
 fn check_line(line: Line3d) {
-    if line.start().z != line.end().z {
+    if (line.start().z - line.end().z).abs() > 1e-7 {
         panic!("Line {} was not on a z= plane.", line)
     }
 }
@@ -97,38 +120,107 @@ pub(super) fn offset_line_endpoint2(line: Line2d, offset: f64, start: bool) -> P
 
 /// Given a line, and possibly two lines that join it, offset the line by
 /// offset.
-pub(super) fn offset_line2(line: Line2d, prev: Option<Line2d>, next: Option<Line2d>, offset: f64) -> Line2d {
+pub(crate) fn offset_line2(line: Line2d, prev: Option<Line2d>, next: Option<Line2d>, offset: f64) -> Line2d {
     let new_start = offset_start_point2(prev, line, offset);
     let new_end = offset_end_point2(line, next, offset);
 
     return Line2d::new(new_start, new_end);
 }
 
-pub(crate) fn offset_line(line: Line3d, prev: Option<Line3d>, next: Option<Line3d>, offset: f64) -> Line3d {
-    if prev.is_some() {
-        check_lines(prev.unwrap(), line)
-    }
-    if next.is_some() {
-        check_lines(line, next.unwrap())
+/// The points the offset edge passes through going round the corner between
+/// `prev` and `next`. This is just the offset intersection unless it has been
+/// bevelled or rounded.
+pub(super) fn offset_corner2(prev: Line2d, next: Line2d, offset: f64, join: JoinStyle) -> Vec<Point2d> {
+    let (vect, angle) = match angle_bisector2(prev, next) {
+        Err(msg) => panic!("{}", msg),
+        Ok(bisector) => bisector,
+    };
+    let corner = next.start;
+    // how far the mitred point would be from the corner, in offsets
+    let ratio = 1.0 / angle.sin();
+    let (pd, nd) = (prev.direction(), next.direction());
+    // positive offsets are to the right, so that side is on the outside of left turns
+    let outside = (pd.x * nd.y - pd.y * nd.x) * offset > EPS;
+    let limit = match join {
+        JoinStyle::Mitre { limit } => limit,
+        _ => INSIDE_LIMIT,
+    };
+    let mitre = corner.add(vect.scale(offset / angle.sin()));
+    if !outside {
+        return vec![if ratio <= limit {
+            mitre
+        } else {
+            corner.add(vect.scale(offset * limit))
+        }];
     }
-    let mut new = offset_line2(
-        line.to2d(),
-        prev.map(|x| x.to2d()),
-        next.map(|x| x.to2d()),
-        offset,
-    );
-    if new.direction().unit().add(line.to2d().direction().unit()).norm() < 1e-8 {
-        new = Line2d::new(new.end, new.start)
+    let bevel_start = offset_line_endpoint2(prev, offset, false);
+    let bevel_end = offset_line_endpoint2(next, offset, true);
+    match join {
+        JoinStyle::Mitre { limit } if ratio <= limit => vec![mitre],
+        JoinStyle::Mitre { .. } | JoinStyle::Bevel => vec![bevel_start, bevel_end],
+        JoinStyle::Round { steps } => {
+            let (from, to) = (bevel_start.sub(corner), bevel_end.sub(corner));
+            let turn = (from.x * to.y - from.y * to.x).atan2(from.dot(to));
+            let steps = steps.max(1);
+            (0..=steps)
+                .map(|i| {
+                    let (s, c) = (turn * i as f64 / steps as f64).sin_cos();
+                    corner.add(Point2d::new(from.x * c - from.y * s, from.x * s + from.y * c))
+                })
+                .collect()
+        }
     }
-    Line3d::from2d(new, line.start().z)
 }
 
-impl Line3d {
-    pub(crate) fn thicken(self, thickness: f64, prev: Option<Line3d>, next: Option<Line3d>) -> ThickLine3d {
-        ThickLine3d::new(
-            self,
-            offset_line(self, prev, next, thickness / 2.0),
-            offset_line(self, prev, next, -thickness / 2.0),
-        )
+/// Offsets a whole layer of lines, joining them at corners with `join`.
+/// `offsets` has one entry for each point on the layer (so one more than there
+/// are lines), and the offset edge tapers between them. Returns the offset of
+/// each line, along with the points the offset edge goes through between the
+/// end of each line and the start of the next.
+pub(crate) fn offset_layer(
+    lines: &[Line3d],
+    offsets: &[f64],
+    join: JoinStyle,
+) -> (Vec<Line3d>, Vec<Vec<Point3d>>) {
+    if offsets.len() != lines.len() + 1 {
+        panic!(
+            "Need {} offsets for {} lines, got {}",
+            lines.len() + 1,
+            lines.len(),
+            offsets.len()
+        );
+    }
+    for i in 0..lines.len() {
+        check_line(lines[i]);
+        if i > 0 {
+            check_lines(lines[i - 1], lines[i]);
+        }
+    }
+    let z = lines[0].start().z;
+    let flat: Vec<Line2d> = lines.iter().map(|l| l.to2d()).collect();
+    let corners: Vec<Vec<Point2d>> = (1..flat.len())
+        .map(|i| offset_corner2(flat[i - 1], flat[i], offsets[i], join))
+        .collect();
+
+    let mut offset_lines = vec![];
+    for i in 0..flat.len() {
+        let start = match i {
+            0 => offset_line_endpoint2(flat[i], offsets[0], true),
+            _ => *corners[i - 1].last().unwrap(),
+        };
+        let end = match corners.get(i) {
+            None => offset_line_endpoint2(flat[i], offsets[i + 1], false),
+            Some(corner) => corner[0],
+        };
+        let mut new = Line2d::new(start, end);
+        if new.direction().unit().add(flat[i].direction().unit()).norm() < 1e-8 {
+            new = Line2d::new(new.end, new.start)
+        }
+        offset_lines.push(Line3d::from2d(new, z));
     }
+    let corner_points = corners
+        .iter()
+        .map(|c| c.iter().map(|p| Point3d::from2d(*p, z)).collect())
+        .collect();
+    (offset_lines, corner_points)
 }
@@ -37,7 +37,7 @@ impl Point3d {
         return Point3d::new(
             a.y * b.z - a.z * b.y,
             a.z * b.x - a.x * b.z,
-            a.x * b.y - a.y * b.z,
+            a.x * b.y - a.y * b.x,
         );
     }
 
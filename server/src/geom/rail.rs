@@ -0,0 +1,98 @@
+use super::line::*;
+use super::point::*;
+
+/// The path a point takes when moving from one layer to the next while
+/// drawing the surface in between.
+///
+/// A straight rail gives the ruled surfaces joins have always used, while a
+/// smooth one is a Catmull-Rom spline that also passes through the
+/// corresponding points on the layers either side, so the surface doesn't
+/// crease at each layer.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Rail {
+    Straight(Line3d),
+    Smooth {
+        points: [Point3d; 4],
+        from: f64,
+        to: f64,
+    },
+}
+
+impl Rail {
+    pub(crate) fn straight(from: Point3d, to: Point3d) -> Rail {
+        Rail::Straight(Line3d::new(from, to))
+    }
+
+    /// A rail from `from` to `to`, curving to meet `before` (beyond `from`) and
+    /// `after` (beyond `to`). Missing points are reflected so the spline
+    /// carries straight on at that end.
+    pub(crate) fn smooth(
+        before: Option<Point3d>,
+        from: Point3d,
+        to: Point3d,
+        after: Option<Point3d>,
+    ) -> Rail {
+        let p0 = before.unwrap_or_else(|| from.scale(2.0).sub(to));
+        let p3 = after.unwrap_or_else(|| to.scale(2.0).sub(from));
+        Rail::Smooth {
+            points: [p0, from, to, p3],
+            from: 0.0,
+            to: 1.0,
+        }
+    }
+
+    pub(crate) fn point(&self, t: f64) -> Point3d {
+        match self {
+            Rail::Straight(line) => line.point(t),
+            Rail::Smooth { points, from, to } => catmull_rom(points, from + t * (to - from)),
+        }
+    }
+
+    /// The part of this rail between `start` and `end` (as fractions of it).
+    pub(crate) fn section(&self, start: f64, end: f64) -> Rail {
+        match *self {
+            Rail::Straight(line) => Rail::Straight(line.section(start, end)),
+            Rail::Smooth { points, from, to } => Rail::Smooth {
+                points,
+                from: from + start * (to - from),
+                to: from + end * (to - from),
+            },
+        }
+    }
+
+    /// The same rail, travelled the other way.
+    pub(crate) fn reversed(&self) -> Rail {
+        match *self {
+            Rail::Straight(line) => Rail::straight(line.end(), line.start()),
+            Rail::Smooth { points, from, to } => Rail::Smooth {
+                points: [points[3], points[2], points[1], points[0]],
+                from: 1.0 - to,
+                to: 1.0 - from,
+            },
+        }
+    }
+}
+
+// uniform Catmull-Rom spline between points[1] (t = 0) and points[2] (t = 1)
+fn catmull_rom(points: &[Point3d; 4], t: f64) -> Point3d {
+    let [p0, p1, p2, p3] = *points;
+    // exactly, so the surfaces either side of a layer meet it at the same points
+    if t == 0.0 {
+        return p1;
+    } else if t == 1.0 {
+        return p2;
+    }
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p1.scale(2.0)
+        .add(p2.sub(p0).scale(t))
+        .add(
+            p0.scale(2.0)
+                .sub(p1.scale(5.0))
+                .add(p2.scale(4.0))
+                .sub(p3)
+                .scale(t2),
+        )
+        .add(p1.scale(3.0).sub(p0).sub(p2.scale(3.0)).add(p3).scale(t3))
+        .scale(0.5)
+}
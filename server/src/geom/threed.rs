@@ -170,19 +170,9 @@ impl Plane3d {
     /// requires point to be on plane
     pub(crate) fn project_point(&self, point: Point3d) -> Point2d {
         let diff = point.sub(self.origin);
-        return if diff.norm() < EPS {
-            Point2d::new(0.0, 0.0)
-        } else if diff.unit().sub(self.x).norm() < EPS {
-            Point2d::new(diff.norm(), 0.0)
-        } else if diff.unit().sub(self.y).norm() < EPS {
-            Point2d::new(0.0, diff.norm())
-        } else {
-            let len = diff.norm();
-            let cos_angle = diff.dot(self.x) / len;
-            let alpha = len * cos_angle;
-            let beta = (len * len - alpha * alpha).sqrt();
-            Point2d::new(alpha, beta)
-        };
+        // x and y are at right angles and of length 1, so this is all it takes,
+        // and keeps the sign of y for points on the other side of the x axis
+        Point2d::new(diff.dot(self.x), diff.dot(self.y))
     }
     pub(crate) fn project_line(&self, line: Line3d) -> Line2d {
         Line2d::new(self.project_point(line.start()), self.project_point(line.end()))
@@ -1,5 +1,6 @@
 use super::line::Line2d;
 use super::point::Point2d;
+use std::time::Instant;
 
 #[derive(Copy, Clone)]
 pub(crate) struct Tri2d {
@@ -37,3 +38,149 @@ impl Tri2d {
         !(has_neg && has_pos)
     }
 }
+
+// > 0 if o -> a -> b turns anticlockwise
+fn turn(o: Point2d, a: Point2d, b: Point2d) -> f64 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+/// Twice the signed area of a polygon, positive if it goes anticlockwise.
+pub(crate) fn polygon_area(points: &[Point2d]) -> f64 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum()
+}
+
+/// The convex hull of a set of points, anticlockwise (Andrew's monotone chain).
+pub(crate) fn convex_hull(points: &[Point2d]) -> Vec<Point2d> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| {
+        a.x.partial_cmp(&b.x)
+            .unwrap()
+            .then(a.y.partial_cmp(&b.y).unwrap())
+    });
+    if sorted.len() < 3 {
+        return sorted;
+    }
+    let mut hull: Vec<Point2d> = vec![];
+    // lower hull, then upper
+    for pass in 0..2 {
+        let start = hull.len();
+        for p in sorted.iter() {
+            while hull.len() >= start + 2 && turn(hull[hull.len() - 2], hull[hull.len() - 1], *p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*p);
+        }
+        // the last point is the first of the next pass
+        hull.pop();
+        if pass == 0 {
+            sorted.reverse();
+        }
+    }
+    hull
+}
+
+fn segments_cross(a: Point2d, b: Point2d, c: Point2d, d: Point2d) -> bool {
+    let (d1, d2) = (turn(a, b, c), turn(a, b, d));
+    let (d3, d4) = (turn(c, d, a), turn(c, d, b));
+    ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+}
+
+fn same_point(a: Point2d, b: Point2d) -> bool {
+    a.sub(b).norm() < 1e-12
+}
+
+// joins a hole into the outside loop by a pair of edges to the nearest vertex
+// that can be seen from the hole without crossing anything
+fn bridge_hole(outer: &[Point2d], hole: &[Point2d], others: &[Vec<Point2d>]) -> Option<Vec<Point2d>> {
+    let m = (0..hole.len())
+        .max_by(|&i, &j| hole[i].x.partial_cmp(&hole[j].x).unwrap())
+        .unwrap();
+    let from = hole[m];
+    let crosses_loop = |loop_: &[Point2d], to: Point2d| {
+        (0..loop_.len()).any(|i| segments_cross(from, to, loop_[i], loop_[(i + 1) % loop_.len()]))
+    };
+    let mut candidates: Vec<usize> = (0..outer.len()).collect();
+    candidates.sort_by(|&i, &j| {
+        let di = outer[i].sub(from).norm();
+        let dj = outer[j].sub(from).norm();
+        di.partial_cmp(&dj).unwrap()
+    });
+    let v = candidates.into_iter().find(|&i| {
+        let to = outer[i];
+        !crosses_loop(outer, to) && !crosses_loop(hole, to) && !others.iter().any(|o| crosses_loop(o, to))
+    })?;
+    let mut joined = outer[..=v].to_vec();
+    joined.extend(hole[m..].iter());
+    joined.extend(hole[..=m].iter());
+    joined.extend(outer[v..].iter());
+    Some(joined)
+}
+
+/// Splits a polygon with holes into triangles (each anticlockwise), by ear
+/// clipping. The polygon and holes must be simple and not touch each other.
+/// Returns None if that fails, or if it's still going at `deadline`.
+pub(crate) fn triangulate_polygon(
+    outer: &[Point2d],
+    holes: &[Vec<Point2d>],
+    deadline: Option<Instant>,
+) -> Option<Vec<[Point2d; 3]>> {
+    let mut points = outer.to_vec();
+    if polygon_area(&points) < 0.0 {
+        points.reverse();
+    }
+    let mut holes: Vec<Vec<Point2d>> = holes
+        .iter()
+        .map(|h| {
+            let mut h = h.clone();
+            if polygon_area(&h) > 0.0 {
+                h.reverse();
+            }
+            h
+        })
+        .collect();
+    // bridge in holes from the right, so earlier bridges can't block later ones
+    holes.sort_by(|a, b| {
+        let max_x = |h: &Vec<Point2d>| h.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+        max_x(b).partial_cmp(&max_x(a)).unwrap()
+    });
+    for i in 0..holes.len() {
+        points = bridge_hole(&points, &holes[i], &holes[i + 1..])?;
+    }
+
+    let mut tris = vec![];
+    while points.len() > 3 {
+        // each ear takes a look at every point, so this can take a while
+        if matches!(deadline, Some(d) if Instant::now() > d) {
+            return None;
+        }
+        let n = points.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            if turn(a, b, c) <= 0.0 {
+                return false;
+            }
+            // no other vertex (ignoring copies made by bridging) can be inside the ear,
+            // or on its sides, and only reflex ones could be
+            let in_ear = |p: Point2d| turn(a, b, p) >= 0.0 && turn(b, c, p) >= 0.0 && turn(c, a, p) >= 0.0;
+            !(0..n).any(|j| {
+                let p = points[j];
+                turn(points[(j + n - 1) % n], p, points[(j + 1) % n]) <= 0.0
+                    && !same_point(p, a)
+                    && !same_point(p, b)
+                    && !same_point(p, c)
+                    && in_ear(p)
+            })
+        })?;
+        tris.push([points[(ear + n - 1) % n], points[ear], points[(ear + 1) % n]]);
+        points.remove(ear);
+    }
+    if points.len() == 3 && turn(points[0], points[1], points[2]) > 0.0 {
+        tris.push([points[0], points[1], points[2]]);
+    }
+    Some(tris)
+}
@@ -0,0 +1,127 @@
+//! Guards against requests that would take too long or use too much memory,
+//! checked before the expensive parts of generation run.
+
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+
+/// The most one request is allowed to ask for.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Limits {
+    /// lines in all the layers sent, before anything is done to them
+    pub max_input_lines: usize,
+    /// as estimated from the layers and steps, before joining them up
+    pub max_triangles: usize,
+    /// how long generation can take before it gives up
+    pub timeout_seconds: f64,
+    /// in a rendered preview
+    pub max_preview_pixels: usize,
+    /// added between each pair of layers
+    pub max_intermediate_layers: usize,
+    /// how many lines smoothing can turn each line into
+    pub max_smoothing_lines: usize,
+    /// in a rounded join or end
+    pub max_round_steps: usize,
+    /// in a curved corner turning all the way back on itself
+    pub max_curve_steps: usize,
+    /// of the whole model, made by symmetry
+    pub max_symmetry_copies: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input_lines: 200_000,
+            max_triangles: 20_000_000,
+            timeout_seconds: 120.0,
+            max_preview_pixels: 4_000_000,
+            max_intermediate_layers: 16,
+            max_smoothing_lines: 64,
+            max_round_steps: 64,
+            max_curve_steps: 64,
+            max_symmetry_copies: 64,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub enum LimitError {
+    TooManyLines {
+        lines: usize,
+        limit: usize,
+    },
+    TooManyTriangles {
+        estimate: usize,
+        limit: usize,
+    },
+    TimedOut {
+        seconds: f64,
+        limit: f64,
+    },
+    TooManyPixels {
+        pixels: usize,
+        limit: usize,
+    },
+    /// a setting that asks for more detail than is allowed
+    TooMuchDetail {
+        setting: String,
+        value: usize,
+        limit: usize,
+    },
+    /// settings that can't be used, or can't be used together
+    Invalid {
+        reason: String,
+    },
+}
+
+impl LimitError {
+    pub fn status(&self) -> Status {
+        match self {
+            LimitError::TooManyLines { .. } => Status::PayloadTooLarge,
+            LimitError::TooManyTriangles { .. } => Status::UnprocessableEntity,
+            LimitError::TimedOut { .. } => Status::RequestTimeout,
+            LimitError::TooManyPixels { .. } => Status::UnprocessableEntity,
+            LimitError::TooMuchDetail { .. } => Status::UnprocessableEntity,
+            LimitError::Invalid { .. } => Status::BadRequest,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            LimitError::TooManyLines { lines, limit } => format!(
+                "The fractal has {} lines, but at most {} are allowed. Try fewer iterations.",
+                lines, limit
+            ),
+            LimitError::TooManyTriangles { estimate, limit } => format!(
+                "The model would have about {} triangles, but at most {} are allowed. Try fewer \
+                 iterations, fewer steps or a smaller step scale.",
+                estimate, limit
+            ),
+            LimitError::TimedOut { seconds, limit } => format!(
+                "Generating the model took over {:.1}s, and the limit is {:.1}s.",
+                seconds, limit
+            ),
+            LimitError::TooManyPixels { pixels, limit } => format!(
+                "The preview would have {} pixels, but at most {} are allowed. Try a smaller width or height.",
+                pixels, limit
+            ),
+            LimitError::TooMuchDetail { setting, value, limit } => format!(
+                "The model asks for {} {}, but at most {} are allowed.",
+                value, setting, limit
+            ),
+            LimitError::Invalid { reason } => reason.clone(),
+        }
+    }
+}
+
+/// At most how many triangles joining up layers of `counts` lines, with `steps`
+/// between each pair, will make, with `copies` of it all. Each line can be
+/// joined to each line of the layer below it overlaps, and every join is a strip
+/// of two triangles per step (on both sides, for thick lines).
+pub fn estimate_triangles(counts: &[usize], steps: &[i64], thick: bool, copies: usize) -> usize {
+    let per_step = if thick { 4.0 } else { 2.0 };
+    let estimate: f64 = (1..counts.len())
+        .map(|i| (counts[i - 1] + counts[i]) as f64 * steps[i - 1].max(0) as f64 * per_step)
+        .sum();
+    (estimate * copies as f64).min(usize::MAX as f64) as usize
+}
@@ -2,92 +2,650 @@
 extern crate log;
 extern crate simplelog;
 use simplelog::*;
+mod cache;
+mod config;
+mod download;
+mod dxf;
 mod geom;
+mod limits;
+mod outline;
+mod paths;
+mod render;
 mod simple;
+mod stl;
+mod svg;
+mod telemetry;
+#[cfg(test)]
+mod tests;
 #[macro_use]
 extern crate rocket;
 extern crate rocket_contrib;
-use geom::{Layer, Line3d, Point3d, Tri3d};
-use log::info;
+use cache::Cache;
+use config::Config;
+use download::{AcceptEncoding, Encoding};
+use geom::{
+    CapStyle, JoinStyle, Layer, Line, Line3d, Point3d, Segment3d, ThickLine3d, ThickSegment3d, Tri3d, TriSink,
+};
+use limits::{estimate_triangles, LimitError, Limits};
+use log::{info, warn};
+use outline::LayerOutline;
+use paths::SvgLayers;
+use render::Camera;
+use rocket::config::Environment;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::LenientForm;
+use rocket::{Response, State};
 use rocket_contrib::msgpack::MsgPack;
 use rocket_contrib::serve::StaticFiles;
 use serde::{Deserialize, Serialize};
 use simple::*;
-use std::time::Instant;
-use stl_io::*;
-
-fn tris_to_binary_stl(tris: Vec<Tri3d>) -> Vec<u8> {
-    let mesh: Vec<Triangle> = tris
-        .into_iter()
-        .map(|t| stl_io::Triangle {
-            normal: [t.n.x as f32, t.n.y as f32, t.n.z as f32],
-            vertices: [
-                [t.a.x as f32, t.a.y as f32, t.a.z as f32],
-                [t.b.x as f32, t.b.y as f32, t.b.z as f32],
-                [t.c.x as f32, t.c.y as f32, t.c.z as f32],
-            ],
-        })
-        .collect();
-    let mut binary_stl = Vec::<u8>::new();
-    stl_io::write_stl(&mut binary_stl, mesh.iter()).unwrap();
-    binary_stl
-}
-
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Cursor, Write};
+use std::time::{Duration, Instant};
+use stl::StlWriter;
+use telemetry::{Metrics, Report};
 
 #[derive(Deserialize, Serialize)]
 struct Data {
+    #[serde(default)]
     layers: Vec<Vec<[f64; 6]>>,
     holes: HoleOptions,
+    #[serde(default)]
+    transform: LayerTransform,
+    #[serde(default)]
+    symmetry: Symmetry,
+    #[serde(default)]
+    base: Option<BaseOptions>,
+    #[serde(default)]
+    cap: CapStyle,
+    #[serde(default)]
+    join: JoinStyle,
+    #[serde(default)]
+    thickness_profile: ThicknessProfile,
+    // these replace the matching query parameters if given
+    #[serde(default)]
+    thickness_schedule: Option<Schedule>,
+    #[serde(default)]
+    curve_schedule: Option<Schedule>,
+    #[serde(default)]
+    steps_schedule: Option<Schedule>,
+    #[serde(default)]
+    layer_smoothing: Option<LayerSmoothing>,
+    #[serde(default)]
+    correspondence: Correspondence,
+    // replaces `layers` if given
+    #[serde(default)]
+    svg_layers: Option<SvgLayers>,
 }
-#[post(
-    "/stl?<thicken>&<top_thickness>&<bottom_thickness>&<curve>&<max_curve_frac>&<curve_steps_mult>&<init_steps>&<step_scale>&<extrude>&<extrude_dist>",
-    format = "msgpack",
-    data = "<tuple>"
-)]
-fn stl(
-    tuple: MsgPack<Data>,
-    thicken: bool,
+
+/// The settings that come in the query string, which fall back to the ones in
+/// the config file if they aren't given.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Params {
+    pub thicken: bool,
+    pub top_thickness: f64,
+    pub bottom_thickness: f64,
+    pub curve: bool,
+    pub max_curve_frac: f64,
+    pub curve_steps_mult: f64,
+    pub init_steps: i64,
+    pub step_scale: f64,
+    pub extrude: bool,
+    pub extrude_dist: f64,
+    pub smooth: bool,
+    pub intermediate_layers: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            thicken: false,
+            top_thickness: 0.05,
+            bottom_thickness: 0.05,
+            curve: false,
+            max_curve_frac: 0.3,
+            curve_steps_mult: 7.0,
+            init_steps: 12,
+            step_scale: 1.0,
+            extrude: false,
+            extrude_dist: 0.1,
+            smooth: false,
+            intermediate_layers: 0,
+        }
+    }
+}
+
+/// The query parameters that set up generation, any of which can be left out
+/// to use the defaults from the config.
+#[derive(FromForm)]
+struct Query {
+    thicken: Option<bool>,
     top_thickness: Option<f64>,
     bottom_thickness: Option<f64>,
     curve: Option<bool>,
     max_curve_frac: Option<f64>,
     curve_steps_mult: Option<f64>,
-    init_steps: i64,
-    step_scale: f64,
-    extrude: bool,
+    init_steps: Option<i64>,
+    step_scale: Option<f64>,
+    extrude: Option<bool>,
     extrude_dist: Option<f64>,
-) -> Vec<u8> {
-    tris_to_binary_stl(create_triangles(
-        tuple,
-        thicken,
-        top_thickness,
-        bottom_thickness,
-        curve,
-        max_curve_frac,
-        curve_steps_mult,
-        init_steps,
-        step_scale,
-        extrude,
-        extrude_dist,
-    ))
+    smooth: Option<bool>,
+    intermediate_layers: Option<usize>,
 }
 
-fn create_triangles(
+impl Query {
+    fn params(&self, defaults: &Params) -> Params {
+        Params {
+            thicken: self.thicken.unwrap_or(defaults.thicken),
+            top_thickness: self.top_thickness.unwrap_or(defaults.top_thickness),
+            bottom_thickness: self.bottom_thickness.unwrap_or(defaults.bottom_thickness),
+            curve: self.curve.unwrap_or(defaults.curve),
+            max_curve_frac: self.max_curve_frac.unwrap_or(defaults.max_curve_frac),
+            curve_steps_mult: self.curve_steps_mult.unwrap_or(defaults.curve_steps_mult),
+            init_steps: self.init_steps.unwrap_or(defaults.init_steps),
+            step_scale: self.step_scale.unwrap_or(defaults.step_scale),
+            extrude: self.extrude.unwrap_or(defaults.extrude),
+            extrude_dist: self.extrude_dist.unwrap_or(defaults.extrude_dist),
+            smooth: self.smooth.unwrap_or(defaults.smooth),
+            intermediate_layers: self.intermediate_layers.unwrap_or(defaults.intermediate_layers),
+        }
+    }
+}
+
+#[post("/stl?<archive>&<query..>", format = "msgpack", data = "<tuple>")]
+fn stl(
+    defaults: State<Params>,
+    limits: State<Limits>,
+    cache: State<Cache>,
+    metrics: State<Metrics>,
+    encoding: AcceptEncoding,
     tuple: MsgPack<Data>,
-    thicken: bool,
-    top_thickness: Option<f64>,
-    bottom_thickness: Option<f64>,
-    curve: Option<bool>,
-    max_curve_frac: Option<f64>,
-    curve_steps_mult: Option<f64>,
-    init_steps: i64,
-    step_scale: f64,
-    extrude: bool,
-    extrude_dist: Option<f64>,
-) -> Vec<Tri3d> {
-    let data = tuple.into_inner();
+    archive: Option<bool>,
+    query: LenientForm<Query>,
+) -> Response<'static> {
+    let params = query.params(&defaults);
+    let data = match read_data(tuple, &limits) {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let key = cache::key(&(&data, &params));
+    // kept for the archive, as `data` is used up generating the model
+    let inputs = if archive.unwrap_or(false) {
+        Some(serde_json::to_vec_pretty(&serde_json::json!({ "data": &data, "params": &params })).unwrap())
+    } else {
+        None
+    };
+    let stl_name = format!("{}.stl", key);
+    let (file, report) = match cache.get(&stl_name) {
+        Some(file) => {
+            metrics.record_hit();
+            (file, cached_report(&cache, &stl_name))
+        }
+        None => match generate(data, &params, &limits, &cache, &stl_name) {
+            Ok((file, report)) => {
+                metrics.record(&report);
+                keep_report(&cache, &stl_name, &report);
+                (file, Some(report))
+            }
+            Err(response) => return response,
+        },
+    };
+    match inputs {
+        Some(inputs) => archive_response(&cache, &key, file, &inputs, report.as_ref()),
+        None => stl_response(file, report.as_ref(), encoding.0),
+    }
+}
+
+/// Generates the model straight into a file, which is kept in the cache as
+/// `name` if there is one.
+fn generate(
+    data: Data,
+    params: &Params,
+    limits: &Limits,
+    cache: &Cache,
+    name: &str,
+) -> Result<(File, Report), Response<'static>> {
+    let entry = cache
+        .entry(name)
+        .map_err(|e| server_error(&format!("Couldn't create a file for the model: {}", e)))?;
+    let mut writer = match entry
+        .writer()
+        .and_then(|file| StlWriter::new(BufWriter::new(file)))
+    {
+        Ok(writer) => writer,
+        Err(e) => {
+            entry.discard();
+            return Err(server_error(&format!("Couldn't write the model: {}", e)));
+        }
+    };
+    let mut report = match create_triangles(&data, params, limits, &mut writer) {
+        Ok(report) => report,
+        Err(error) => {
+            entry.discard();
+            return Err(refused(error));
+        }
+    };
+    let written = report.time("stl", || writer.finish().map(drop));
+    let finished = match written {
+        Ok(()) => entry.finish(),
+        Err(e) => {
+            entry.discard();
+            Err(e)
+        }
+    };
+    match finished {
+        Ok(file) => Ok((file, report)),
+        Err(e) => Err(server_error(&format!("Couldn't write the model: {}", e))),
+    }
+}
+
+fn stl_response(file: File, report: Option<&Report>, encoding: Option<Encoding>) -> Response<'static> {
+    let mut response = Response::build();
+    response
+        .header(ContentType::Binary)
+        .raw_header("Vary", "Accept-Encoding");
+    if let Some(report) = report {
+        response.header(report_header(report));
+    }
+    match encoding {
+        Some(encoding) => match download::encode(file, encoding) {
+            Ok(body) => response
+                .raw_header("Content-Encoding", encoding.name())
+                .streamed_body(body),
+            Err(e) => return server_error(&format!("Couldn't compress the model: {}", e)),
+        },
+        None => response.sized_body(file),
+    };
+    response.finalize()
+}
+
+/// A zip of the model, `inputs` and the full report if the model was just
+/// made, which is also kept in the cache.
+fn archive_response(
+    cache: &Cache,
+    key: &str,
+    stl: File,
+    inputs: &[u8],
+    report: Option<&Report>,
+) -> Response<'static> {
+    let name = format!("{}.zip", key);
+    let report = report.map(|r| serde_json::to_vec_pretty(r).unwrap());
+    let zip = match cache.get(&name) {
+        Some(zip) => Ok(zip),
+        None => cache.entry(&name).and_then(|entry| {
+            match entry
+                .writer()
+                .and_then(|out| download::archive(stl, inputs, report.as_deref(), out))
+            {
+                Ok(()) => entry.finish(),
+                Err(e) => {
+                    entry.discard();
+                    Err(e)
+                }
+            }
+        }),
+    };
+    match zip {
+        Ok(zip) => Response::build()
+            .header(ContentType::Zip)
+            .raw_header("Content-Disposition", "attachment; filename=\"fractal.zip\"")
+            .sized_body(zip)
+            .finalize(),
+        Err(e) => server_error(&format!("Couldn't make the archive: {}", e)),
+    }
+}
+
+/// The posted data, with any SVG paths turned into layers.
+fn read_data(tuple: MsgPack<Data>, limits: &Limits) -> Result<Data, Response<'static>> {
+    let mut data = tuple.into_inner();
+    if let Some(svg_layers) = data.svg_layers.take() {
+        data.layers = svg_layers.layers(limits.max_input_lines).map_err(|error| {
+            info!("Couldn't read the SVG paths: {}", error);
+            let body = serde_json::json!({ "error": error.to_string(), "details": error });
+            Response::build()
+                .status(Status::BadRequest)
+                .header(ContentType::JSON)
+                .sized_body(Cursor::new(body.to_string()))
+                .finalize()
+        })?;
+    }
+    Ok(data)
+}
+
+fn refused(error: LimitError) -> Response<'static> {
+    info!("Refused to generate: {}", error.message());
+    let body = serde_json::json!({ "error": error.message(), "details": error });
+    Response::build()
+        .status(error.status())
+        .header(ContentType::JSON)
+        .sized_body(Cursor::new(body.to_string()))
+        .finalize()
+}
+
+/// The outline of each layer as SVG, or just layer `layer` (counting from 1 at
+/// the top) if it's given.
+#[post("/svg?<layer>&<query..>", format = "msgpack", data = "<tuple>")]
+fn svg(
+    defaults: State<Params>,
+    limits: State<Limits>,
+    tuple: MsgPack<Data>,
+    layer: Option<usize>,
+    query: LenientForm<Query>,
+) -> Response<'static> {
+    let outlines = read_data(tuple, &limits)
+        .and_then(|data| numbered_outlines(data, &query.params(&defaults), &limits, layer));
+    match outlines {
+        Ok(outlines) => Response::build()
+            .header(ContentType::SVG)
+            .sized_body(Cursor::new(svg::layers_svg(&outlines)))
+            .finalize(),
+        Err(response) => response,
+    }
+}
+
+/// The outlines the model is made from as a DXF, or just layer `layer` if it's
+/// given.
+#[post("/dxf?<layer>&<query..>", format = "msgpack", data = "<tuple>")]
+fn dxf(
+    defaults: State<Params>,
+    limits: State<Limits>,
+    tuple: MsgPack<Data>,
+    layer: Option<usize>,
+    query: LenientForm<Query>,
+) -> Response<'static> {
+    let params = query.params(&defaults);
+    let outlines =
+        read_data(tuple, &limits).and_then(|data| numbered_outlines(data, &params, &limits, layer));
+    match outlines {
+        Ok(outlines) => Response::build()
+            .header(ContentType::new("image", "vnd.dxf"))
+            .raw_header("Content-Disposition", "attachment; filename=\"fractal.dxf\"")
+            .sized_body(Cursor::new(dxf::layers_dxf(
+                &outlines,
+                params.intermediate_layers + 1,
+            )))
+            .finalize(),
+        Err(response) => response,
+    }
+}
+
+/// A picture of the model as a PNG, from the camera given by the rest of the
+/// query (see `render::Camera` for what each means).
+#[post(
+    "/png?<width>&<height>&<azimuth>&<elevation>&<fov>&<query..>",
+    format = "msgpack",
+    data = "<tuple>"
+)]
+fn png(
+    defaults: State<Params>,
+    limits: State<Limits>,
+    cache: State<Cache>,
+    metrics: State<Metrics>,
+    tuple: MsgPack<Data>,
+    width: Option<u32>,
+    height: Option<u32>,
+    azimuth: Option<f64>,
+    elevation: Option<f64>,
+    fov: Option<f64>,
+    query: LenientForm<Query>,
+) -> Response<'static> {
+    let params = query.params(&defaults);
+    let data = match read_data(tuple, &limits) {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let default = Camera::default();
+    let camera = Camera {
+        width: width.unwrap_or(default.width).max(1),
+        height: height.unwrap_or(default.height).max(1),
+        azimuth: azimuth.unwrap_or(default.azimuth),
+        elevation: elevation.unwrap_or(default.elevation),
+        fov: fov.unwrap_or(default.fov),
+    };
+    let pixels = camera.width as usize * camera.height as usize;
+    if pixels > limits.max_preview_pixels {
+        return refused(LimitError::TooManyPixels {
+            pixels,
+            limit: limits.max_preview_pixels,
+        });
+    }
+
+    let name = format!("{}.png", cache::key(&(&data, &params, &camera)));
+    if let Some(file) = cache.get(&name) {
+        metrics.record_hit();
+        let mut response = Response::build();
+        response.header(ContentType::PNG);
+        if let Some(report) = cached_report(&cache, &name) {
+            response.header(report_header(&report));
+        }
+        return response.sized_body(file).finalize();
+    }
+    // the model is made twice, first to find where the camera goes and then
+    // to draw each triangle as it's made, so it's never all kept at once
+    let start = Instant::now();
+    let mut bounds = render::Bounds::default();
+    let measured = match create_triangles(&data, &params, &limits, &mut bounds) {
+        Ok(report) => report,
+        Err(error) => return refused(error),
+    };
+    let deadline = start + Duration::from_secs_f64(limits.timeout_seconds);
+    let mut renderer = render::Renderer::new(&bounds, &camera, Some(deadline));
+    let mut report = match create_triangles(&data, &params, &limits, &mut renderer) {
+        Ok(report) => report,
+        Err(error) => return refused(error),
+    };
+    report
+        .stages
+        .insert(0, ("measure".to_string(), measured.total_seconds()));
+    let png = match renderer.finish() {
+        Some(image) => report.time("encode", || image.png()),
+        None => {
+            return refused(LimitError::TimedOut {
+                seconds: report.total_seconds(),
+                limit: limits.timeout_seconds,
+            })
+        }
+    };
+    metrics.record(&report);
+    if let Err(e) = cache.write(&name, &png) {
+        warn!("Couldn't cache the preview: {}", e);
+    }
+    keep_report(&cache, &name, &report);
+    Response::build()
+        .header(ContentType::PNG)
+        .header(report_header(&report))
+        .sized_body(Cursor::new(png))
+        .finalize()
+}
+
+// the report on making the file cached as `name`, if it's been kept
+fn cached_report(cache: &Cache, name: &str) -> Option<Report> {
+    let bytes = cache.read(&format!("{}.report.json", name))?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn keep_report(cache: &Cache, name: &str, report: &Report) {
+    let bytes = serde_json::to_vec(report).unwrap();
+    if let Err(e) = cache.write(&format!("{}.report.json", name), &bytes) {
+        warn!("Couldn't cache the report: {}", e);
+    }
+}
+
+fn report_header(report: &Report) -> Header<'static> {
+    Header::new(
+        "X-Generation-Report",
+        serde_json::to_string(&report.summary()).unwrap(),
+    )
+}
+
+// the outlines numbered from 1, keeping only `layer` if it's given. Layers added
+// in between iterations are numbered too, so aren't skipped over.
+fn numbered_outlines(
+    data: Data,
+    params: &Params,
+    limits: &Limits,
+    layer: Option<usize>,
+) -> Result<Vec<(usize, LayerOutline)>, Response<'static>> {
+    let outlines = create_outlines(data, params, limits).map_err(refused)?;
+    let count = outlines.len();
+    let numbered: Vec<(usize, LayerOutline)> = outlines
+        .into_iter()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .filter(|(i, _)| layer.map_or(true, |layer| layer == *i))
+        .collect();
+    if numbered.is_empty() {
+        return Err(Response::build()
+            .status(Status::NotFound)
+            .header(ContentType::Plain)
+            .sized_body(Cursor::new(format!("There are only {} layers", count)))
+            .finalize());
+    }
+    Ok(numbered)
+}
 
-    let mut layers: Vec<Layer<Line3d>> = data
+fn server_error(message: &str) -> Response<'static> {
+    warn!("{}", message);
+    Response::build()
+        .status(Status::InternalServerError)
+        .header(ContentType::Plain)
+        .sized_body(Cursor::new(message.to_string()))
+        .finalize()
+}
+
+#[get("/metrics")]
+fn metrics(metrics: State<Metrics>) -> String {
+    metrics.render()
+}
+
+/// Generates the model, putting the triangles into `sink`.
+fn create_triangles(
+    data: &Data,
+    params: &Params,
+    limits: &Limits,
+    sink: &mut dyn TriSink,
+) -> Result<Report, LimitError> {
+    let deadline = Instant::now() + Duration::from_secs_f64(limits.timeout_seconds);
+    check_inputs(data, params, limits)?;
+    let mut report = Report::default();
+    let layers = input_layers(data, limits, &mut report)?;
+    let (layers, maps) = prepare_layers(layers, data, params, limits, deadline, &mut report)?;
+
+    let count = layers.len();
+    let steps: Vec<i64> = match &data.steps_schedule {
+        Some(schedule) => schedule
+            .values(count.saturating_sub(1))
+            .into_iter()
+            .map(|s| (s.round() as i64).max(1))
+            .collect(),
+        None => {
+            let mut steps = vec![params.init_steps];
+            for i in 1..count.saturating_sub(1) {
+                steps.push((steps[i - 1] as f64 * params.step_scale).round() as i64);
+            }
+            steps
+        }
+    };
+    let curve_fracs = curve_fracs(data, params, count);
+
+    // symmetry and the base need the whole model, so then it's kept until
+    // they're done, and otherwise it goes straight into the sink
+    let whole_model = !data.symmetry.is_none() || data.base.is_some();
+    let mut tris: Vec<Tri3d> = vec![];
+    let target: &mut dyn TriSink = if whole_model { &mut tris } else { &mut *sink };
+    let pairs = if params.thicken {
+        let mut thickened = report.time("thicken", || {
+            thick_segment_layers(&thicken_layers(&layers, data, params))
+        });
+        if let Some(curve_fracs) = &curve_fracs {
+            thickened = report.time("curve", || {
+                simple::curve_layers(thickened, curve_fracs, params.curve_steps_mult)
+            });
+        };
+        info!("Done curve generation");
+        check_deadline(deadline, &report, limits)?;
+        report.developed_lines = line_count(&thickened);
+        // arcs are joined up as the lines they're drawn with
+        let counts: Vec<usize> = thickened
+            .iter()
+            .map(|l| l.lines().iter().map(|s| s.pieces() as usize).sum())
+            .collect();
+        check_triangles(
+            estimate_triangles(&counts, &steps, true, data.symmetry.count()),
+            limits,
+        )?;
+        report.time("develop", || {
+            simple::develop(
+                thickened,
+                &maps,
+                data.holes,
+                &steps,
+                if params.extrude { params.extrude_dist } else { 0.0 },
+                params.smooth,
+                data.cap,
+                Some(deadline),
+                target,
+            )
+        })
+    } else {
+        let mut segments = segment_layers(&layers);
+        if let Some(curve_fracs) = &curve_fracs {
+            segments = report.time("curve", || {
+                simple::curve_layers(segments, curve_fracs, params.curve_steps_mult)
+            });
+        };
+        check_deadline(deadline, &report, limits)?;
+        report.developed_lines = line_count(&segments);
+        // arcs are joined up as the lines they're drawn with
+        let counts: Vec<usize> = segments
+            .iter()
+            .map(|l| l.lines().iter().map(|s| s.pieces() as usize).sum())
+            .collect();
+        check_triangles(
+            estimate_triangles(&counts, &steps, false, data.symmetry.count()),
+            limits,
+        )?;
+        report.time("develop", || {
+            simple::develop(
+                segments,
+                &maps,
+                data.holes,
+                &steps,
+                if params.extrude { params.extrude_dist } else { 0.0 },
+                params.smooth,
+                data.cap,
+                Some(deadline),
+                target,
+            )
+        })
+    };
+    report.layer_pairs = pairs;
+    check_deadline(deadline, &report, limits)?;
+    if whole_model {
+        tris = report.time("symmetry", || simple::apply_symmetry(tris, &data.symmetry));
+        check_deadline(deadline, &report, limits)?;
+        if let Some(base) = &data.base {
+            let based = report.time("base", || simple::add_base(tris, base, Some(deadline)));
+            check_deadline(deadline, &report, limits)?;
+            tris = based.map_err(|reason| LimitError::Invalid { reason })?;
+        }
+        sink.push_all(tris);
+    }
+    report.triangles = sink.count();
+    info!(
+        "Calculated {} in {:.2}s",
+        if params.thicken { "thick" } else { "thin" },
+        report.total_seconds()
+    );
+    Ok(report)
+}
+
+/// The layers in `data`, as long as there aren't too many lines in them and
+/// none of them are empty.
+fn input_layers(data: &Data, limits: &Limits, report: &mut Report) -> Result<Vec<Layer<Line3d>>, LimitError> {
+    let layers: Vec<Layer<Line3d>> = data
         .layers
         .iter()
         .map(|l| {
@@ -103,55 +661,327 @@ fn create_triangles(
             )
         })
         .collect();
-    let start = Instant::now();
-    layers = simple::simplify(layers);
+    report.input_lines = line_count(&layers);
+    if report.input_lines > limits.max_input_lines {
+        return Err(LimitError::TooManyLines {
+            lines: report.input_lines,
+            limit: limits.max_input_lines,
+        });
+    }
+    // everything after this needs somewhere on each layer to start from
+    if let Some(i) = layers.iter().position(|l| !positive(l.length())) {
+        return Err(LimitError::Invalid {
+            reason: format!("Layer {} has no length, so there's nothing to join to it.", i + 1),
+        });
+    }
+    Ok(layers)
+}
+
+/// Everything done to the layers before they're thickened or curved, along
+/// with the maps saying which parts of each are joined to the layer below.
+fn prepare_layers(
+    mut layers: Vec<Layer<Line3d>>,
+    data: &Data,
+    params: &Params,
+    limits: &Limits,
+    deadline: Instant,
+    report: &mut Report,
+) -> Result<(Vec<Layer<Line3d>>, Vec<FracMap>), LimitError> {
+    // simplifying and transforming don't move anything along the layers, so
+    // these stay the same through them
+    let mut maps = data.correspondence.maps(&layers);
+    layers = report.time("simplify", || simple::simplify(layers));
+    report.simplified_lines = line_count(&layers);
+    // the layers in between can have as many lines as the layers either side
+    // of them put together
+    let smoothed = data.layer_smoothing.map_or(1, |s| s.lines_per_line());
+    let lines = report
+        .simplified_lines
+        .saturating_mul(1 + 2 * params.intermediate_layers)
+        .saturating_mul(smoothed);
+    check_lines(lines, limits)?;
+    let (morphed, morphed_maps) = report.time("morph", || {
+        simple::morph_layers(layers, maps, params.intermediate_layers)
+    });
+    layers = report.time("transform", || {
+        simple::transform_layers(morphed, &data.transform, params.intermediate_layers + 1)
+    });
+    maps = morphed_maps;
+    check_deadline(deadline, report, limits)?;
+    if let Some(smoothing) = &data.layer_smoothing {
+        let (smoothed, moved) = report
+            .time("smooth", || simple::smooth_layers(layers, smoothing))
+            .map_err(|reason| LimitError::Invalid { reason })?;
+        layers = smoothed;
+        maps = maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| map.through(&moved[i + 1], &moved[i]))
+            .collect();
+        check_deadline(deadline, report, limits)?;
+    }
+    Ok((layers, maps))
+}
 
-    let tris: Vec<Tri3d> = if thicken {
-        let mut thickened = vec![];
-        let t = top_thickness.unwrap();
-        let b = bottom_thickness.unwrap();
-        for i in 0..layers.len() {
-            thickened.push(layers[i].thicken(t - (t - b) * (i as f64) / (layers.len() as f64 - 1.0)))
+fn curve_fracs(data: &Data, params: &Params, count: usize) -> Option<Vec<f64>> {
+    if params.curve {
+        Some(match &data.curve_schedule {
+            Some(schedule) => schedule.values(count),
+            None => vec![params.max_curve_frac; count],
+        })
+    } else {
+        None
+    }
+}
+
+fn thicken_layers(layers: &[Layer<Line3d>], data: &Data, params: &Params) -> Vec<Layer<ThickLine3d>> {
+    let thicknesses = match &data.thickness_schedule {
+        Some(schedule) => schedule.values(layers.len()),
+        None => Schedule::Linear {
+            from: params.top_thickness,
+            to: params.bottom_thickness,
         }
-        if curve.is_some() && curve.unwrap() {
-            thickened = simple::curve_layers(thickened, max_curve_frac.unwrap(), curve_steps_mult.unwrap())
-        };
-        info!("Done curve generation");
-        simple::develop(
-            thickened,
-            data.holes,
-            init_steps,
-            step_scale,
-            if extrude { extrude_dist.unwrap() } else { 0.0 },
-        )
+        .values(layers.len()),
+    };
+    layers
+        .iter()
+        .zip(&thicknesses)
+        .map(|(layer, &thickness)| {
+            // so a control point inside a line isn't missed
+            layer
+                .split_at(&data.thickness_profile.fractions())
+                .thicken(|frac| thickness * data.thickness_profile.scale(frac), data.join)
+        })
+        .collect()
+}
+
+// so that curves stay as arcs until they're drawn
+fn segment_layers(layers: &[Layer<Line3d>]) -> Vec<Layer<Segment3d>> {
+    layers
+        .iter()
+        .map(|l| Layer::new(l.lines().iter().map(|&line| Segment3d::Line(line)).collect()))
+        .collect()
+}
+
+// the same, for thickened layers
+fn thick_segment_layers(layers: &[Layer<ThickLine3d>]) -> Vec<Layer<ThickSegment3d>> {
+    layers
+        .iter()
+        .map(|l| Layer::new(l.lines().iter().map(|&line| ThickSegment3d::Line(line)).collect()))
+        .collect()
+}
+
+/// The outline of each layer, thickened and curved as they would be for the
+/// model.
+fn create_outlines(data: Data, params: &Params, limits: &Limits) -> Result<Vec<LayerOutline>, LimitError> {
+    let deadline = Instant::now() + Duration::from_secs_f64(limits.timeout_seconds);
+    check_inputs(&data, params, limits)?;
+    let mut report = Report::default();
+    let layers = input_layers(&data, limits, &mut report)?;
+    let (layers, _) = prepare_layers(layers, &data, params, limits, deadline, &mut report)?;
+    let mut outlines: Vec<LayerOutline> = layers.iter().map(LayerOutline::new).collect();
+    let curve_fracs = curve_fracs(&data, params, layers.len());
+    if params.thicken {
+        let thickened = report.time("thicken", || {
+            thick_segment_layers(&thicken_layers(&layers, &data, params))
+        });
+        check_deadline(deadline, &report, limits)?;
+        check_lines(line_count(&thickened), limits)?;
+        for (outline, layer) in outlines.iter_mut().zip(&thickened) {
+            outline.add_thick(layer, false);
+        }
+        if let Some(curve_fracs) = &curve_fracs {
+            let curved = report.time("curve", || {
+                simple::curve_layers(thickened, curve_fracs, params.curve_steps_mult)
+            });
+            check_deadline(deadline, &report, limits)?;
+            check_lines(line_count(&curved), limits)?;
+            for (outline, layer) in outlines.iter_mut().zip(&curved) {
+                outline.add_thick(layer, true);
+            }
+        }
+    } else if let Some(curve_fracs) = &curve_fracs {
+        let curved = report.time("curve", || {
+            simple::curve_layers(segment_layers(&layers), curve_fracs, params.curve_steps_mult)
+        });
+        check_deadline(deadline, &report, limits)?;
+        check_lines(line_count(&curved), limits)?;
+        for (outline, layer) in outlines.iter_mut().zip(&curved) {
+            outline.add_curved(layer);
+        }
+    }
+    info!("Made outlines in {:.2}s", report.total_seconds());
+    Ok(outlines)
+}
+
+/// Refuses settings that can't be used together, or that ask for more detail
+/// than `limits` allow, before any work is done.
+fn check_inputs(data: &Data, params: &Params, limits: &Limits) -> Result<(), LimitError> {
+    let invalid = |reason: &str| {
+        Err(LimitError::Invalid {
+            reason: reason.to_string(),
+        })
+    };
+    if params.curve && !positive(params.curve_steps_mult) {
+        return invalid("The number of lines curves are drawn with has to be more than 0.");
+    }
+    let round_steps = |steps: i64| steps.max(0) as usize;
+    let join_steps = match data.join {
+        JoinStyle::Round { steps } => round_steps(steps),
+        _ => 0,
+    };
+    let cap_steps = match data.cap {
+        CapStyle::Round { steps } => round_steps(steps),
+        _ => 0,
+    };
+    // a corner turns by less than half a turn, and the arc round it is drawn
+    // with `curve_steps_mult` lines per radian
+    let curve_steps = if params.curve {
+        (params.curve_steps_mult * std::f64::consts::PI).ceil() as usize
     } else {
-        if curve.is_some() && curve.unwrap() {
-            layers = simple::curve_layers(layers, max_curve_frac.unwrap(), curve_steps_mult.unwrap())
-        };
-        simple::develop(
-            layers,
-            data.holes,
-            init_steps,
-            step_scale,
-            if extrude { extrude_dist.unwrap() } else { 0.0 },
-        )
+        0
     };
-    info!(
-        "Calculated {} in {:.2}s",
-        if thicken { "thick" } else { "thin" },
-        start.elapsed().as_secs_f32()
-    );
-    tris
+    let details = [
+        (
+            "layers in between each iteration",
+            params.intermediate_layers,
+            limits.max_intermediate_layers,
+        ),
+        (
+            "lines each line is smoothed into",
+            data.layer_smoothing.map_or(1, |s| s.lines_per_line()),
+            limits.max_smoothing_lines,
+        ),
+        ("lines round each join", join_steps, limits.max_round_steps),
+        ("lines round each end", cap_steps, limits.max_round_steps),
+        (
+            "lines round each curved corner",
+            curve_steps,
+            limits.max_curve_steps,
+        ),
+        (
+            "copies of the model",
+            data.symmetry.copies,
+            limits.max_symmetry_copies,
+        ),
+    ];
+    for &(setting, value, limit) in details.iter() {
+        if value > limit {
+            return Err(LimitError::TooMuchDetail {
+                setting: setting.to_string(),
+                value,
+                limit,
+            });
+        }
+    }
+    if params.smooth {
+        if let HoleOptions::ParallelOnly { .. } = data.holes {
+            return invalid(
+                "Holes between parallel lines can't be used with smoothing, as they're cut from flat \
+                 pieces. Use holes everywhere, or turn smoothing off.",
+            );
+        }
+    }
+    let schedules = [
+        ("thickness", &data.thickness_schedule),
+        ("curve", &data.curve_schedule),
+        ("steps", &data.steps_schedule),
+    ];
+    for (name, schedule) in schedules.iter() {
+        if let Some(Err(reason)) = schedule.as_ref().map(|s| s.check()) {
+            return invalid(&format!("The {} schedule {}.", name, reason));
+        }
+    }
+    if let CapStyle::Chamfer { size } = data.cap {
+        // any more and the corners cut off meet in the middle
+        if !positive(size) || size > 0.5 {
+            return invalid("The chamfer on the ends has to be more than 0, and at most 0.5.");
+        }
+    }
+    if !positive(data.transform.scale) {
+        return invalid("The scale between layers has to be more than 0.");
+    }
+    if let Some(base) = &data.base {
+        if !positive(base.margin) || !positive(base.thickness) {
+            return invalid("The base's margin and thickness have to be more than 0.");
+        }
+        if !params.thicken {
+            return invalid(
+                "A base can only be added to a thickened model, as a thin one has no inside to join \
+                 up with it.",
+            );
+        }
+    }
+    Ok(())
+}
+
+fn positive(x: f64) -> bool {
+    x.is_finite() && x > 0.0
+}
+
+/// Gives up if generation has gone on past `deadline`.
+fn check_deadline(deadline: Instant, report: &Report, limits: &Limits) -> Result<(), LimitError> {
+    if Instant::now() > deadline {
+        Err(LimitError::TimedOut {
+            seconds: report.total_seconds(),
+            limit: limits.timeout_seconds,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Refuses layers with more lines than the model could be made from, as each
+/// line makes at least two triangles.
+fn check_lines(lines: usize, limits: &Limits) -> Result<(), LimitError> {
+    check_triangles(lines.saturating_mul(2), limits)
+}
+
+fn check_triangles(estimate: usize, limits: &Limits) -> Result<(), LimitError> {
+    if estimate > limits.max_triangles {
+        Err(LimitError::TooManyTriangles {
+            estimate,
+            limit: limits.max_triangles,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn line_count<T>(layers: &[Layer<T>]) -> usize
+where
+    T: Line + Copy + std::fmt::Debug + std::fmt::Display,
+{
+    layers.iter().map(|l| l.count()).sum()
 }
 
 fn main() {
-    TermLogger::init(LevelFilter::Info, Config::default(), TerminalMode::Mixed).unwrap();
-
-    rocket::ignite()
-        .mount("/api", routes![stl])
-        .mount(
-            "/",
-            StaticFiles::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../web/dist")),
-        )
+    let config = Config::load().unwrap_or_else(|e| panic!("{}", e));
+    let level = config.log_level().unwrap_or_else(|e| panic!("{}", e));
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![TermLogger::new(
+        level,
+        simplelog::Config::default(),
+        TerminalMode::Mixed,
+    )];
+    if let Some(path) = &config.log_file {
+        let file = OpenOptions::new().create(true).append(true).open(path).unwrap();
+        loggers.push(WriteLogger::new(level, simplelog::Config::default(), file));
+    }
+    CombinedLogger::init(loggers).unwrap();
+
+    let cache = config.cache();
+    let rocket_config = rocket::config::Config::build(Environment::active().unwrap())
+        .address(config.address)
+        .port(config.port)
+        .finalize()
+        .unwrap();
+    rocket::custom(rocket_config)
+        .manage(Metrics::default())
+        .manage(config.limits)
+        .manage(config.defaults)
+        .manage(cache)
+        .mount("/api", routes![stl, svg, dxf, png])
+        .mount("/", routes![metrics])
+        .mount("/", StaticFiles::from(config.static_dir))
         .launch();
 }
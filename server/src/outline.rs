@@ -0,0 +1,168 @@
+//! The 2D outlines of each layer, as the server made them, for drawing or
+//! cutting them out rather than printing the whole model.
+
+use crate::geom::*;
+
+const GAP: f64 = 1e-9;
+
+/// Part of a path, going on from where the last one ended.
+#[derive(Debug, Clone, Copy)]
+pub enum Part {
+    Line(Point2d),
+    /// going `sweep` radians round `centre` (anticlockwise if positive)
+    Arc {
+        end: Point2d,
+        centre: Point2d,
+        sweep: f64,
+    },
+}
+
+impl Part {
+    pub fn end(&self) -> Point2d {
+        match *self {
+            Part::Line(end) => end,
+            Part::Arc { end, .. } => end,
+        }
+    }
+}
+
+/// A connected run of lines and arcs.
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub start: Point2d,
+    pub parts: Vec<Part>,
+}
+
+impl Path {
+    pub fn end(&self) -> Point2d {
+        self.parts.last().map_or(self.start, |p| p.end())
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point2d> + '_ {
+        std::iter::once(self.start).chain(self.parts.iter().map(|p| p.end()))
+    }
+}
+
+// adds `part`, starting a new path if it doesn't carry on from the last one
+fn extend(paths: &mut Vec<Path>, start: Point2d, part: Part) {
+    if start.sub(part.end()).norm() < GAP {
+        if let Part::Line(_) = part {
+            return;
+        }
+    }
+    match paths.last_mut() {
+        Some(path) if path.end().sub(start).norm() < GAP => path.parts.push(part),
+        _ => paths.push(Path {
+            start,
+            parts: vec![part],
+        }),
+    }
+}
+
+fn line_paths<I: IntoIterator<Item = Line3d>>(lines: I) -> Vec<Path> {
+    let mut paths = vec![];
+    for line in lines {
+        extend(&mut paths, line.start().to2d(), Part::Line(line.end().to2d()));
+    }
+    paths
+}
+
+fn segment_paths(segments: &[Segment3d]) -> Vec<Path> {
+    let mut paths = vec![];
+    for segment in segments {
+        let part = match segment {
+            Segment3d::Line(line) => Part::Line(line.end().to2d()),
+            Segment3d::Arc(arc) => Part::Arc {
+                end: arc.end().to2d(),
+                centre: arc.centre().to2d(),
+                sweep: arc.sweep(),
+            },
+        };
+        extend(&mut paths, segment.start().to2d(), part);
+    }
+    paths
+}
+
+/// Which of the layer's lines a path is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Centre,
+    Outer,
+    Inner,
+    CurvedCentre,
+    CurvedOuter,
+    CurvedInner,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Centre => "centre",
+            Kind::Outer => "outer",
+            Kind::Inner => "inner",
+            Kind::CurvedCentre => "curved-centre",
+            Kind::CurvedOuter => "curved-outer",
+            Kind::CurvedInner => "curved-inner",
+        }
+    }
+
+    fn is_curved(self) -> bool {
+        matches!(self, Kind::CurvedCentre | Kind::CurvedOuter | Kind::CurvedInner)
+    }
+
+    fn is_edge(self) -> bool {
+        !matches!(self, Kind::Centre | Kind::CurvedCentre)
+    }
+}
+
+/// Everything drawn for one layer, which is flat at height `z`.
+#[derive(Debug, Clone)]
+pub struct LayerOutline {
+    pub z: f64,
+    pub paths: Vec<(Kind, Vec<Path>)>,
+}
+
+impl LayerOutline {
+    /// Starts with the centreline of `layer`, before it's thickened or curved.
+    pub fn new(layer: &Layer<Line3d>) -> Self {
+        LayerOutline {
+            z: layer.first().start().z,
+            paths: vec![(Kind::Centre, line_paths(layer.lines().iter().copied()))],
+        }
+    }
+
+    /// Adds the edges of `layer`, and its centre too if it's been curved.
+    pub(crate) fn add_thick(&mut self, layer: &Layer<ThickSegment3d>, curved: bool) {
+        let segments = layer.lines();
+        if curved {
+            let centre: Vec<Segment3d> = segments.iter().map(|s| s.centre()).collect();
+            self.paths.push((Kind::CurvedCentre, segment_paths(&centre)));
+        }
+        let (outer, inner) = if curved {
+            (Kind::CurvedOuter, Kind::CurvedInner)
+        } else {
+            (Kind::Outer, Kind::Inner)
+        };
+        let outers: Vec<Segment3d> = segments.iter().flat_map(|s| s.outer()).collect();
+        let inners: Vec<Segment3d> = segments.iter().flat_map(|s| s.inner()).collect();
+        self.paths.push((outer, segment_paths(&outers)));
+        self.paths.push((inner, segment_paths(&inners)));
+    }
+
+    /// The lines the model is made from: the edges if the layer's been
+    /// thickened and the centre if not, curved if it's been curved.
+    pub fn finished(&self) -> impl Iterator<Item = &Path> {
+        let curved = self.paths.iter().any(|(kind, _)| kind.is_curved());
+        let thick = self.paths.iter().any(|(kind, _)| kind.is_edge());
+        self.paths
+            .iter()
+            .filter(move |(kind, _)| kind.is_curved() == curved && kind.is_edge() == thick)
+            .flat_map(|(_, paths)| paths)
+    }
+
+    /// Adds the centreline after it's been curved, with the curves as arcs.
+    pub fn add_curved(&mut self, layer: &Layer<Segment3d>) {
+        self.paths
+            .push((Kind::CurvedCentre, segment_paths(layer.lines())));
+    }
+}
@@ -0,0 +1,292 @@
+//! Reading layers from SVG path data, so they can be drawn by hand rather than
+//! with an L-system. Coordinates are used as they are, so y goes the other
+//! way to how an SVG shows it.
+
+use crate::geom::{Line, Line3d, Point2d, Point3d};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Peekable;
+
+/// The most lines a curve is split into, however small the tolerance.
+const MAX_CURVE_LINES: usize = 1000;
+/// The smallest tolerance allowed, as anything smaller can't be printed.
+const MIN_TOLERANCE: f64 = 1e-4;
+
+fn default_tolerance() -> f64 {
+    0.01
+}
+
+/// One path for each layer, from the top down.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SvgLayers {
+    /// the `d` attribute of each path, which has to be drawn in one go
+    pub paths: Vec<String>,
+    /// the height of each layer
+    pub heights: Vec<f64>,
+    /// how far the lines curves are split into can be from the curves
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathError {
+    /// counting from 1
+    pub layer: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Layer {}: {}", self.layer, self.message)
+    }
+}
+
+impl SvgLayers {
+    /// The layers, in the same form as they're sent from the web client, as
+    /// long as there are no more than `max_lines` lines in them all.
+    pub fn layers(&self, max_lines: usize) -> Result<Vec<Vec<[f64; 6]>>, PathError> {
+        if self.paths.len() != self.heights.len() {
+            return Err(PathError {
+                layer: self.paths.len().min(self.heights.len()) + 1,
+                message: format!(
+                    "There are {} paths and {} heights, which have to match",
+                    self.paths.len(),
+                    self.heights.len()
+                ),
+            });
+        }
+        if !(self.tolerance.is_finite() && self.tolerance >= MIN_TOLERANCE) {
+            return Err(PathError {
+                layer: 1,
+                message: format!("The tolerance has to be at least {}", MIN_TOLERANCE),
+            });
+        }
+        let mut layers = vec![];
+        let mut lines_left = max_lines;
+        for (i, (path, &z)) in self.paths.iter().zip(&self.heights).enumerate() {
+            let error = |message: String| PathError {
+                layer: i + 1,
+                message,
+            };
+            if !z.is_finite() {
+                return Err(error("The height has to be a number".to_string()));
+            }
+            let lines = parse_path(path, z, self.tolerance, lines_left).map_err(error)?;
+            lines_left -= lines.len();
+            layers.push(
+                lines
+                    .iter()
+                    .map(|l| {
+                        let (s, e) = (l.start(), l.end());
+                        [s.x, s.y, s.z, e.x, e.y, e.z]
+                    })
+                    .collect(),
+            );
+        }
+        Ok(layers)
+    }
+}
+
+// splits path data into commands and numbers
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+enum Token {
+    Command(char),
+    Number(f64),
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rest = self
+            .rest
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let c = self.rest.chars().next()?;
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            self.rest = &self.rest[1..];
+            return Some(Ok(Token::Command(c)));
+        }
+        // a number ends at anything that can't carry it on, which includes a
+        // second `.` or a sign that isn't after an exponent
+        let bytes = self.rest.as_bytes();
+        let mut end = 0;
+        let (mut seen_dot, mut seen_exp) = (false, false);
+        while end < bytes.len() {
+            let b = bytes[end];
+            let ok = match b {
+                b'0'..=b'9' => true,
+                b'+' | b'-' => end == 0 || matches!(bytes[end - 1], b'e' | b'E'),
+                b'.' if !seen_dot && !seen_exp => {
+                    seen_dot = true;
+                    true
+                }
+                b'e' | b'E' if !seen_exp && end > 0 => {
+                    seen_exp = true;
+                    true
+                }
+                _ => false,
+            };
+            if !ok {
+                break;
+            }
+            end += 1;
+        }
+        let (number, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(match number.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Token::Number(n)),
+            Ok(_) => Err(format!("The number at \"{}\" is too big", truncate(number, rest))),
+            Err(_) => Err(format!("Expected a number at \"{}\"", truncate(number, rest))),
+        })
+    }
+}
+
+fn truncate(number: &str, rest: &str) -> String {
+    format!("{}{}", number, rest).chars().take(12).collect()
+}
+
+// the next `count` numbers, which a command takes
+fn numbers(count: usize, tokens: &mut Peekable<Tokens>) -> Result<Vec<f64>, String> {
+    (0..count)
+        .map(|_| match tokens.next() {
+            Some(Ok(Token::Number(n))) => Ok(n),
+            Some(Err(e)) => Err(e),
+            _ => Err("Expected a number".to_string()),
+        })
+        .collect()
+}
+
+/// The lines drawn by the path data `d`, at height `z`, with any curves split
+/// into lines no further than `tolerance` from them. Stops as soon as there
+/// are more than `max_lines` of them.
+pub fn parse_path(d: &str, z: f64, tolerance: f64, max_lines: usize) -> Result<Vec<Line3d>, String> {
+    let mut tokens = Tokens { rest: d }.peekable();
+    let mut points: Vec<Point2d> = vec![];
+    let mut at = Point2d::new(0.0, 0.0);
+    let mut start = at;
+    let mut command = None;
+    loop {
+        match tokens.peek() {
+            None => break,
+            Some(Ok(Token::Command(c))) => {
+                command = Some(*c);
+                tokens.next();
+            }
+            // numbers after a command repeat it, apart from after a move,
+            // where they're lines
+            Some(Ok(Token::Number(_))) => match command {
+                Some('M') => command = Some('L'),
+                Some('m') => command = Some('l'),
+                Some('Z') | Some('z') | None => return Err("Expected a command".to_string()),
+                _ => {}
+            },
+            Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+        }
+        let c = command.unwrap();
+        if points.is_empty() && c != 'M' && c != 'm' {
+            return Err("Paths have to start with a move".to_string());
+        }
+        let relative = c.is_ascii_lowercase();
+        let offset = if relative { at } else { Point2d::new(0.0, 0.0) };
+        let point = |x: f64, y: f64| Point2d::new(offset.x + x, offset.y + y);
+        match c.to_ascii_uppercase() {
+            'M' => {
+                let n = numbers(2, &mut tokens)?;
+                at = point(n[0], n[1]);
+                if !points.is_empty() && points[points.len() - 1].sub(at).norm() > 0.0 {
+                    return Err(
+                        "Each layer has to be one path, so it can only move before drawing".to_string(),
+                    );
+                }
+                start = at;
+                if points.is_empty() {
+                    points.push(at);
+                }
+            }
+            'L' => {
+                let n = numbers(2, &mut tokens)?;
+                at = point(n[0], n[1]);
+                points.push(at);
+            }
+            'H' => {
+                let n = numbers(1, &mut tokens)?;
+                at = Point2d::new(n[0] + if relative { at.x } else { 0.0 }, at.y);
+                points.push(at);
+            }
+            'V' => {
+                let n = numbers(1, &mut tokens)?;
+                at = Point2d::new(at.x, n[0] + if relative { at.y } else { 0.0 });
+                points.push(at);
+            }
+            'Q' => {
+                let n = numbers(4, &mut tokens)?;
+                let (control, end) = (point(n[0], n[1]), point(n[2], n[3]));
+                points.extend(flatten(&[at, control, end], tolerance));
+                at = end;
+            }
+            'C' => {
+                let n = numbers(6, &mut tokens)?;
+                let (c1, c2, end) = (point(n[0], n[1]), point(n[2], n[3]), point(n[4], n[5]));
+                points.extend(flatten(&[at, c1, c2, end], tolerance));
+                at = end;
+            }
+            'Z' => {
+                at = start;
+                points.push(at);
+            }
+            other => return Err(format!("The {} command isn't supported", other)),
+        }
+        if points.len() > max_lines.saturating_add(1) {
+            return Err(format!(
+                "The paths draw more than {} lines between them, which is the most allowed",
+                max_lines
+            ));
+        }
+    }
+
+    let lines: Vec<Line3d> = points
+        .windows(2)
+        .filter(|w| w[0].sub(w[1]).norm() > 0.0)
+        .map(|w| Line3d::new(Point3d::new(w[0].x, w[0].y, z), Point3d::new(w[1].x, w[1].y, z)))
+        .collect();
+    if lines.is_empty() {
+        return Err("The path doesn't draw anything".to_string());
+    }
+    Ok(lines)
+}
+
+// the points after the first along a quadratic or cubic Bézier curve, close
+// enough together that the lines between them are within `tolerance` of it
+fn flatten(controls: &[Point2d], tolerance: f64) -> Vec<Point2d> {
+    let second_diff = |a: Point2d, b: Point2d, c: Point2d| a.sub(b.scale(2.0)).add(c).norm();
+    // the most a line can be from the curve, times the number of lines squared
+    let error = match controls.len() {
+        3 => second_diff(controls[0], controls[1], controls[2]) / 4.0,
+        _ => {
+            let most = second_diff(controls[0], controls[1], controls[2]).max(second_diff(
+                controls[1],
+                controls[2],
+                controls[3],
+            ));
+            most * 3.0 / 4.0
+        }
+    };
+    let count = ((error / tolerance).sqrt().ceil() as usize).clamp(1, MAX_CURVE_LINES);
+    (1..=count)
+        .map(|i| {
+            let t = i as f64 / count as f64;
+            // de Casteljau
+            let mut points = controls.to_vec();
+            while points.len() > 1 {
+                points = points
+                    .windows(2)
+                    .map(|w| w[0].scale(1.0 - t).add(w[1].scale(t)))
+                    .collect();
+            }
+            points[0]
+        })
+        .collect()
+}
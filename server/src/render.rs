@@ -0,0 +1,276 @@
+//! Draws a mesh on the CPU, for previews where there's no browser or GPU to
+//! show the STL with. Triangles are shaded by how directly they face a light
+//! above and behind the camera, and a depth buffer keeps the nearest in front.
+
+use crate::geom::{Point3d, Tri3d, TriSink};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const COLOUR: [f64; 3] = [110.0, 150.0, 200.0];
+/// how bright the sides facing away from the light are
+const AMBIENT: f64 = 0.25;
+
+/// Where the mesh is looked at from. The camera always points at the middle
+/// of the mesh, and is as close as it can be with all of it in the picture.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Camera {
+    pub width: u32,
+    pub height: u32,
+    /// degrees round the z axis, from looking along y
+    pub azimuth: f64,
+    /// degrees above the xy plane
+    pub elevation: f64,
+    /// degrees the picture spans across its narrower side
+    pub fov: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            width: 512,
+            height: 512,
+            azimuth: 30.0,
+            elevation: 35.0,
+            fov: 30.0,
+        }
+    }
+}
+
+/// A picture, as rows of RGB pixels from the top left.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn png(&self) -> Vec<u8> {
+        let mut png = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+            encoder.set_color(png::ColorType::RGB);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&self.pixels).unwrap();
+        }
+        png
+    }
+}
+
+/// The box the triangles pushed into it fit in, which is all the camera needs
+/// to know about the mesh.
+pub struct Bounds {
+    min: Point3d,
+    max: Point3d,
+    count: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds {
+            min: Point3d::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3d::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            count: 0,
+        }
+    }
+}
+
+impl TriSink for Bounds {
+    fn push(&mut self, tri: Tri3d) {
+        for p in [tri.a, tri.b, tri.c].iter() {
+            self.min = Point3d::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+            self.max = Point3d::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+        }
+        self.count += 1;
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+}
+
+// where the camera is, and which ways are right, up and forward from it
+struct View {
+    eye: Point3d,
+    right: Point3d,
+    up: Point3d,
+    forward: Point3d,
+    // pixels across that are one unit across at one unit away
+    focal: f64,
+    width: f64,
+    height: f64,
+}
+
+impl View {
+    fn new(bounds: &Bounds, camera: &Camera) -> Self {
+        let (min, max) = if bounds.count == 0 {
+            (Point3d::new(0.0, 0.0, 0.0), Point3d::new(0.0, 0.0, 0.0))
+        } else {
+            (bounds.min, bounds.max)
+        };
+        let centre = min.add(max).scale(0.5);
+        let radius = max.sub(centre).norm().max(1e-9);
+
+        let (azimuth, elevation) = (camera.azimuth.to_radians(), camera.elevation.to_radians());
+        let back = Point3d::new(
+            elevation.cos() * azimuth.sin(),
+            -elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+        );
+        let forward = back.scale(-1.0);
+        // looking straight up or down, the top of the picture is along y
+        let right = match forward.cross(Point3d::new(0.0, 0.0, 1.0)) {
+            r if r.norm() > 1e-9 => r.unit(),
+            _ => Point3d::new(1.0, 0.0, 0.0),
+        };
+        let up = right.cross(forward);
+
+        let (width, height) = (camera.width as f64, camera.height as f64);
+        let half_fov = (camera.fov.clamp(1.0, 170.0) / 2.0).to_radians();
+        let focal = width.min(height) / 2.0 / half_fov.tan();
+        View {
+            eye: centre.add(back.scale(radius / half_fov.sin())),
+            right,
+            up,
+            forward,
+            focal,
+            width,
+            height,
+        }
+    }
+
+    // the pixel `p` lands on, and how far in front of the camera it is
+    fn project(&self, p: Point3d) -> (f64, f64, f64) {
+        let v = p.sub(self.eye);
+        let depth = v.dot(self.forward);
+        let scale = self.focal / depth;
+        (
+            self.width / 2.0 + v.dot(self.right) * scale,
+            self.height / 2.0 - v.dot(self.up) * scale,
+            depth,
+        )
+    }
+}
+
+/// Draws the triangles pushed into it as `camera` sees them, giving up on
+/// drawing any more once it's past `deadline`.
+pub struct Renderer {
+    view: View,
+    light: Point3d,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    // one over the depth of what's been drawn at each pixel, which goes
+    // linearly across a triangle on the screen when the depth doesn't
+    nearest: Vec<f64>,
+    deadline: Option<Instant>,
+    timed_out: bool,
+    count: usize,
+}
+
+impl Renderer {
+    /// Ready to draw a mesh that fits in `bounds`.
+    pub fn new(bounds: &Bounds, camera: &Camera, deadline: Option<Instant>) -> Self {
+        let view = View::new(bounds, camera);
+        let light = view
+            .up
+            .scale(0.6)
+            .sub(view.right.scale(0.4))
+            .sub(view.forward)
+            .unit();
+        let (width, height) = (camera.width as usize, camera.height as usize);
+        Renderer {
+            view,
+            light,
+            width,
+            height,
+            pixels: BACKGROUND
+                .iter()
+                .copied()
+                .cycle()
+                .take(width * height * 3)
+                .collect(),
+            nearest: vec![0.0; width * height],
+            deadline,
+            timed_out: false,
+            count: 0,
+        }
+    }
+
+    /// The picture, or nothing if it ran out of time.
+    pub fn finish(self) -> Option<Image> {
+        if self.timed_out {
+            None
+        } else {
+            Some(Image {
+                width: self.width as u32,
+                height: self.height as u32,
+                pixels: self.pixels,
+            })
+        }
+    }
+
+    fn draw(&mut self, tri: &Tri3d) {
+        let normal = tri.b.sub(tri.a).cross(tri.c.sub(tri.a));
+        if normal.norm() == 0.0 {
+            return;
+        }
+        // triangles can face either way, so both sides are lit
+        let brightness = AMBIENT + (1.0 - AMBIENT) * normal.unit().dot(self.light).abs();
+        let mut colour = [0_u8; 3];
+        for (c, base) in colour.iter_mut().zip(COLOUR.iter()) {
+            *c = (base * brightness).round() as u8;
+        }
+
+        let view = &self.view;
+        let [a, b, c] = [view.project(tri.a), view.project(tri.b), view.project(tri.c)];
+        let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        if area == 0.0 {
+            return;
+        }
+        let (width, height) = (self.width, self.height);
+        let left = a.0.min(b.0).min(c.0).floor().max(0.0) as usize;
+        let right = (a.0.max(b.0).max(c.0).ceil().max(0.0) as usize).min(width);
+        let top = a.1.min(b.1).min(c.1).floor().max(0.0) as usize;
+        let bottom = (a.1.max(b.1).max(c.1).ceil().max(0.0) as usize).min(height);
+        for y in top..bottom {
+            // a thin triangle across the picture can take a long time to draw
+            // for how little of it there is, so this is checked each row
+            if matches!(self.deadline, Some(d) if Instant::now() > d) {
+                self.timed_out = true;
+                return;
+            }
+            for x in left..right {
+                // how much of each corner there is at the middle of the pixel
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                let wa = ((b.0 - px) * (c.1 - py) - (b.1 - py) * (c.0 - px)) / area;
+                let wb = ((c.0 - px) * (a.1 - py) - (c.1 - py) * (a.0 - px)) / area;
+                let wc = 1.0 - wa - wb;
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let inverse_depth = wa / a.2 + wb / b.2 + wc / c.2;
+                let i = y * width + x;
+                if inverse_depth > self.nearest[i] {
+                    self.nearest[i] = inverse_depth;
+                    self.pixels[i * 3..i * 3 + 3].copy_from_slice(&colour);
+                }
+            }
+        }
+    }
+}
+
+impl TriSink for Renderer {
+    fn push(&mut self, tri: Tri3d) {
+        if !self.timed_out {
+            self.draw(&tri);
+        }
+        self.count += 1;
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+}
@@ -0,0 +1,175 @@
+use crate::geom::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
+pub enum BaseShape {
+    Rectangle,
+    Circle,
+    ConvexHull,
+}
+
+/// A flat plate to put underneath the model, so it has more to stick to the
+/// print bed with.
+#[derive(Deserialize, Debug, Serialize)]
+pub struct BaseOptions {
+    pub shape: BaseShape,
+    pub thickness: f64,
+    /// how far the plate extends past the bottom of the model, which has to be
+    /// more than 0 so the model can be cut out of the top of it
+    pub margin: f64,
+}
+
+const CIRCLE_STEPS: usize = 64;
+const TOLERANCE: f64 = 1e-7;
+
+fn key(p: Point3d) -> (i64, i64, i64) {
+    let k = |x: f64| (x / TOLERANCE).round() as i64;
+    (k(p.x), k(p.y), k(p.z))
+}
+
+// the outline of the plate, anticlockwise
+fn plate_outline(shape: BaseShape, points: &[Point2d], margin: f64) -> Vec<Point2d> {
+    let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+    let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+    // a polygon with its sides touching the circle, so it's all at least
+    // `radius` from `centre`
+    let around = |centre: Point2d, radius: f64, steps: usize| {
+        let corner = radius / (std::f64::consts::PI / steps as f64).cos();
+        (0..steps).map(move |i| {
+            let (s, c) = (2.0 * std::f64::consts::PI * i as f64 / steps as f64).sin_cos();
+            centre.add(Point2d::new(c * corner, s * corner))
+        })
+    };
+    match shape {
+        BaseShape::Rectangle => vec![
+            Point2d::new(min_x - margin, min_y - margin),
+            Point2d::new(max_x + margin, min_y - margin),
+            Point2d::new(max_x + margin, max_y + margin),
+            Point2d::new(min_x - margin, max_y + margin),
+        ],
+        BaseShape::Circle => {
+            let centre = Point2d::new((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+            let radius = points.iter().map(|p| p.sub(centre).norm()).fold(0.0, f64::max);
+            around(centre, radius + margin, CIRCLE_STEPS).collect()
+        }
+        BaseShape::ConvexHull => {
+            // the hull of a circle around every point is the hull pushed out by margin
+            let expanded: Vec<Point2d> = points
+                .iter()
+                .flat_map(|p| around(*p, margin, CIRCLE_STEPS / 4))
+                .collect();
+            convex_hull(&expanded)
+        }
+    }
+}
+
+// the loops around the edge of a set of triangles, or None if they touch
+// themselves somewhere
+fn boundary_loops(tris: &[Tri3d]) -> Option<Vec<Vec<Point3d>>> {
+    let mut points = HashMap::new();
+    let mut counts = HashMap::new();
+    for t in tris {
+        for (a, b) in [(t.a, t.b), (t.b, t.c), (t.c, t.a)].iter() {
+            let (ka, kb) = (key(*a), key(*b));
+            points.insert(ka, *a);
+            let undirected = if ka < kb { (ka, kb) } else { (kb, ka) };
+            *counts.entry(undirected).or_insert(0) += 1;
+        }
+    }
+    let mut next = HashMap::new();
+    for t in tris {
+        for (a, b) in [(t.a, t.b), (t.b, t.c), (t.c, t.a)].iter() {
+            let (ka, kb) = (key(*a), key(*b));
+            let undirected = if ka < kb { (ka, kb) } else { (kb, ka) };
+            if counts[&undirected] == 1 && next.insert(ka, kb).is_some() {
+                return None;
+            }
+        }
+    }
+    let mut starts: Vec<_> = next.keys().cloned().collect();
+    starts.sort();
+    let mut loops = vec![];
+    for start in starts {
+        if !next.contains_key(&start) {
+            continue;
+        }
+        let mut loop_ = vec![];
+        let mut curr = start;
+        while let Some(after) = next.remove(&curr) {
+            loop_.push(points[&curr]);
+            curr = after;
+        }
+        if curr != start {
+            return None;
+        }
+        loops.push(loop_);
+    }
+    Some(loops)
+}
+
+/// Adds a plate under the model, and joins it on so the two make a single
+/// solid. The bottom face of the model is taken out, and the top of the plate
+/// goes around it. Gives up with the reason if the bottom face can't be cut out
+/// of the plate, or if it's still going at `deadline`.
+///
+/// Only thickened models have a bottom face to join up with the plate: a thin
+/// one is a surface with no inside, so can't be given a base.
+pub fn add_base(
+    tris: Vec<Tri3d>,
+    options: &BaseOptions,
+    deadline: Option<Instant>,
+) -> Result<Vec<Tri3d>, String> {
+    if tris.is_empty() {
+        return Ok(tris);
+    }
+    let z = tris
+        .iter()
+        .flat_map(|t| vec![t.a.z, t.b.z, t.c.z])
+        .fold(f64::INFINITY, f64::min);
+    let on_bottom = |p: Point3d| (p.z - z).abs() < TOLERANCE;
+
+    let footprint: Vec<Point2d> = tris
+        .iter()
+        .flat_map(|t| vec![t.a, t.b, t.c])
+        .filter(|p| on_bottom(*p))
+        .map(|p| p.to2d())
+        .collect();
+    let outline = plate_outline(options.shape, &footprint, options.margin);
+
+    let (bottom_face, mut out): (Vec<Tri3d>, Vec<Tri3d>) = tris
+        .into_iter()
+        .partition(|t| on_bottom(t.a) && on_bottom(t.b) && on_bottom(t.c) && t.n.z < 0.0);
+    if bottom_face.is_empty() {
+        return Err("The model has no bottom face to join the base to.".to_string());
+    }
+    let holes: Vec<Vec<Point2d>> = boundary_loops(&bottom_face)
+        .ok_or("The bottom of the model touches itself, so can't be cut out of the base.")?
+        .iter()
+        .map(|l| l.iter().map(|p| p.to2d()).collect())
+        .collect();
+    let top = triangulate_polygon(&outline, &holes, deadline)
+        .ok_or("Couldn't cut the bottom of the model out of the base.")?;
+
+    let up = |p: Point2d| Point3d::from2d(p, z);
+    let down = |p: Point2d| Point3d::from2d(p, z - options.thickness);
+    for [a, b, c] in top {
+        out.push(Tri3d::from_sp(&Line3d::new(up(a), up(b)), &up(c), false));
+    }
+    for i in 1..outline.len() - 1 {
+        out.push(Tri3d::from_sp(
+            &Line3d::new(down(outline[0]), down(outline[i + 1])),
+            &down(outline[i]),
+            false,
+        ));
+    }
+    for i in 0..outline.len() {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        out.push(Tri3d::from_sp(&Line3d::new(down(a), down(b)), &up(b), false));
+        out.push(Tri3d::from_sp(&Line3d::new(down(a), up(b)), &up(a), false));
+    }
+    Ok(out)
+}
@@ -0,0 +1,159 @@
+use crate::geom::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+/// How the lines of each layer are matched up with the layer below.
+#[derive(Deserialize, Debug, Serialize)]
+pub enum Correspondence {
+    /// the point a fraction of the way along one layer is joined to the point
+    /// the same fraction of the way along the one below, as they're sent
+    ArcLength,
+    /// `parents[i][k]` is the line of layer `i - 1` that line `k` of layer `i`
+    /// grew from (`parents[0]` is ignored), so each group of lines is joined to
+    /// exactly the line it came from. Layers where this doesn't fit fall back to
+    /// arc length.
+    Symbol { parents: Vec<Vec<usize>> },
+}
+
+impl Default for Correspondence {
+    fn default() -> Self {
+        Correspondence::ArcLength
+    }
+}
+
+/// A map from fractions of the way along a layer to fractions of the way along
+/// the layer below, going straight between `knots` of (upper, lower) fractions.
+/// The same is used for a layer before and after it's changed, with the layer
+/// as it was in place of the one below.
+#[derive(Clone, Debug)]
+pub struct FracMap {
+    knots: Vec<(f64, f64)>,
+}
+
+impl FracMap {
+    pub fn identity() -> FracMap {
+        FracMap {
+            knots: vec![(0.0, 0.0), (1.0, 1.0)],
+        }
+    }
+
+    /// The map through `knots`, which are put in order and kept from going
+    /// backwards in either fraction.
+    pub fn new(mut knots: Vec<(f64, f64)>) -> FracMap {
+        knots.retain(|k| k.0.is_finite() && k.1.is_finite());
+        knots.push((0.0, 0.0));
+        knots.push((1.0, 1.0));
+        for k in knots.iter_mut() {
+            *k = (k.0.clamp(0.0, 1.0), k.1.clamp(0.0, 1.0));
+        }
+        knots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut lower = 0.0_f64;
+        for k in knots.iter_mut() {
+            lower = lower.max(k.1);
+            k.1 = lower;
+        }
+        knots.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12);
+        FracMap { knots }
+    }
+
+    /// The fraction of the lower layer that `frac` of the upper one is joined to.
+    pub fn apply(&self, frac: f64) -> f64 {
+        interpolate(&self.knots, frac, |k| k)
+    }
+
+    /// The fraction of the upper layer that `frac` of the lower one is joined to.
+    pub fn invert(&self, frac: f64) -> f64 {
+        interpolate(&self.knots, frac, |(a, b)| (b, a))
+    }
+
+    /// The (upper, lower) pairs of fractions that go together at each of
+    /// `upper`, each of `lower` and each knot, in order along the layers.
+    pub fn pairs(&self, upper: &[f64], lower: &[f64]) -> Vec<(f64, f64)> {
+        let mut pairs: Vec<(f64, f64)> = upper.iter().map(|&u| (u, self.apply(u))).collect();
+        pairs.extend(lower.iter().map(|&l| (self.invert(l), l)));
+        pairs.extend(&self.knots);
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        pairs.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        pairs
+    }
+
+    /// This map between the layers after they've been changed, where `upper`
+    /// and `lower` map the fractions of each layer after to those before.
+    pub fn through(&self, upper: &FracMap, lower: &FracMap) -> FracMap {
+        let mut knots: Vec<(f64, f64)> = upper
+            .knots
+            .iter()
+            .map(|&(after, before)| (after, lower.invert(self.apply(before))))
+            .collect();
+        knots.extend(
+            self.knots
+                .iter()
+                .map(|&(u, l)| (upper.invert(u), lower.invert(l))),
+        );
+        knots.extend(
+            lower
+                .knots
+                .iter()
+                .map(|&(after, before)| (upper.invert(self.invert(before)), after)),
+        );
+        FracMap::new(knots)
+    }
+}
+
+// the knots are increasing in both, so this works either way round
+fn interpolate<F>(knots: &[(f64, f64)], frac: f64, order: F) -> f64
+where
+    F: Fn((f64, f64)) -> (f64, f64),
+{
+    // the first knot after the start that `frac` is at or before
+    let i = 1 + knots[1..].partition_point(|&k| order(k).0 < frac);
+    if i == knots.len() {
+        return order(knots[knots.len() - 1]).1;
+    }
+    let ((x0, y0), (x1, y1)) = (order(knots[i - 1]), order(knots[i]));
+    if x1 - x0 < 1e-12 {
+        y0
+    } else {
+        y0 + (y1 - y0) * (frac - x0) / (x1 - x0)
+    }
+}
+
+impl Correspondence {
+    /// The map from each layer to the one below, so `maps[i - 1]` is for layer
+    /// `i`. These are worked out on the layers as they're sent, before anything
+    /// changes how many lines they have, and then carried through the layers
+    /// added in between and smoothing.
+    pub fn maps(&self, layers: &[Layer<Line3d>]) -> Vec<FracMap> {
+        (1..layers.len())
+            .map(|i| match self {
+                Correspondence::ArcLength => FracMap::identity(),
+                Correspondence::Symbol { parents } => parents
+                    .get(i)
+                    .and_then(|p| symbol_map(&layers[i], &layers[i - 1], p))
+                    .unwrap_or_else(|| {
+                        info!("Layer {} doesn't match its parents, using arc length", i);
+                        FracMap::identity()
+                    }),
+            })
+            .collect()
+    }
+}
+
+fn symbol_map(upper: &Layer<Line3d>, lower: &Layer<Line3d>, parents: &[usize]) -> Option<FracMap> {
+    if parents.len() != upper.count()
+        || parents.iter().any(|&p| p >= lower.count())
+        || parents.windows(2).any(|w| w[1] < w[0])
+    {
+        return None;
+    }
+    let (upper_fracs, lower_fracs) = (upper.fractions(), lower.fractions());
+    let mut knots = vec![(0.0, 0.0)];
+    for k in 1..parents.len() {
+        // a line with no children goes with the group after it
+        if parents[k] != parents[k - 1] && upper_fracs[k] > knots[knots.len() - 1].0 {
+            knots.push((upper_fracs[k], lower_fracs[parents[k - 1] + 1]));
+        }
+    }
+    knots.push((1.0, 1.0));
+    Some(FracMap { knots })
+}
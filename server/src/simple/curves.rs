@@ -23,12 +23,6 @@ fn smallest_angle_between(a: Point3d, b: Point3d) -> f64 {
         a.dot(b).acos()
     }
 }
-// only in x,y
-fn rotate_vector(vec: Point3d, angle: f64) -> Point3d {
-    let (s, c) = angle.sin_cos();
-    Point3d::new(vec.x * c - vec.y * s, vec.x * s + vec.y * c, vec.z)
-}
-
 fn fix_lines<T>(lines: Vec<T>) -> Vec<T>
 where
     T: Line + Copy,
@@ -49,13 +43,26 @@ where
     return out_lines;
 }
 
+const PI: f64 = std::f64::consts::PI;
+
+/// Whether the corner where `prev` meets `next` is curved, which it isn't if
+/// they're in line, or if the curve would be too small to see.
+pub(crate) fn is_filleted(prev: Line3d, next: Line3d, max_curve_frac: f64) -> bool {
+    let angle = smallest_angle_between(prev.direction(), next.direction().scale(-1.0)) % PI;
+    let in_line = angle.abs() < 1e-7 || (angle - PI).abs() < 1e-7;
+    // a circle with no size would just be the corner
+    !in_line && prev.length().min(next.length()) * max_curve_frac >= 1e-8
+}
+
+// the fillet between prev and next, along with the rest of whichever of them
+// `return_next` picks
 fn curve_intersection(
     prev: Line3d,
     next: Line3d,
     max_curve_frac: f64,
     steps_multiplier: f64,
     return_next: bool,
-) -> Vec<Line3d> {
+) -> Vec<Segment3d> {
     // important points
     let a = prev.start();
     let b = prev.end(); // = next.start
@@ -64,12 +71,11 @@ fn curve_intersection(
     let pv = prev.direction();
     let nv = next.direction();
 
-    const PI: f64 = std::f64::consts::PI;
+    if !is_filleted(prev, next, max_curve_frac) {
+        return vec![Segment3d::Line(if return_next { next } else { prev })];
+    }
     // angle between AB & BC
     let angle_between_lines = smallest_angle_between(pv, nv.scale(-1.0)) % PI;
-    if angle_between_lines.abs() < 1e-7 || (angle_between_lines - PI).abs() < 1e-7 {
-        return vec![if return_next { next } else { prev }];
-    };
     let bisect_tangent_angle = (std::f64::consts::PI - angle_between_lines) / 2.0;
     let smaller_side_length = prev.length().min(next.length()) * max_curve_frac;
 
@@ -85,62 +91,51 @@ fn curve_intersection(
     // point at which the circle intersects the angle bisector
     let circle_bis_intersection = centre.add(b.sub(centre).unit().scale(radius));
 
-    let (start_point, _theor_end_point) = if return_next {
+    let start_point = if return_next {
         // i.e. want to go from the bisector to the tangent point with BC
-        (
-            circle_bis_intersection,
-            next.point(smaller_side_length / next.length()),
-        )
+        circle_bis_intersection
     } else {
         // i.e from the tangent point with AB to the bisector
         let start = prev.point(1.0 - smaller_side_length / prev.length());
         // Join A to the tangent point on AB (if they are not the same)
         if start.sub(a).norm() > 1e-8 {
-            lines.push(Line3d::new(a, start));
+            lines.push(Segment3d::Line(Line3d::new(a, start)));
         }
-        (start, circle_bis_intersection)
+        start
     };
-    // uses just the angle and the multiplier so that there are the same no. of
-    // steps in the outside and inside of a thick line
-    let mut steps = (bisect_tangent_angle * steps_multiplier).round() as i64;
-    if steps == 0 {
-        steps = 1
-    }
-    let mut prev_point = start_point;
-    // direction vector from centre to start
-    let init_vector = start_point.sub(centre);
     // to determine whether BC is a anti/clockwise turn from AB
     let perp_2d_prod = pv.x * nv.y - pv.y * nv.x;
-    for i in 1..=steps {
-        let curr_angle = bisect_tangent_angle * (i as f64) / (steps as f64);
-        let new_point = centre.add(rotate_vector(
-            init_vector,
-            // rotate the right way (note that the =0.0 case is ignored since we tested for parallelness of
-            // AB, BC earlier)
-            if perp_2d_prod < 0.0 {
-                -1.0 * curr_angle
-            } else {
-                curr_angle
-            },
-        ));
-        lines.push(Line3d::new(prev_point, new_point));
-        prev_point = new_point;
-    }
+    // uses just the angle and the multiplier so that there are the same no. of
+    // steps in the outside and inside of a thick line (note that the =0.0 case
+    // is ignored since we tested for parallelness of AB, BC earlier)
+    let arc = Arc3d::new(
+        centre,
+        start_point,
+        if perp_2d_prod < 0.0 {
+            -bisect_tangent_angle
+        } else {
+            bisect_tangent_angle
+        },
+        steps_multiplier,
+    );
+    lines.push(Segment3d::Arc(arc));
 
     // Join tangent point to C (if not already there)
-    if return_next && prev_point.sub(c).norm() > 1e-8 {
-        lines.push(Line3d::new(prev_point, c));
+    if return_next && arc.end().sub(c).norm() > 1e-8 {
+        lines.push(Segment3d::Line(Line3d::new(arc.end(), c)));
     }
     lines
 }
 
-pub fn curve_line(
+/// Curves the ends of `line` to meet `prev` and `next`, as straight lines and
+/// arcs.
+pub(crate) fn fillet_line(
     line: Line3d,
     prev: Option<Line3d>,
     next: Option<Line3d>,
     max_curve_frac: f64,
     steps_multiplier: f64,
-) -> Vec<Line3d> {
+) -> Vec<Segment3d> {
     if prev.is_some() && next.is_some() {
         // divide up the line according to the ratio of the angles
         let divided = (line.section(0.0, 0.5), line.section(0.5, 1.0));
@@ -166,7 +161,45 @@ pub fn curve_line(
     } else if next.is_some() {
         curve_intersection(line, next.unwrap(), max_curve_frac, steps_multiplier, false)
     } else {
-        vec![line]
+        vec![Segment3d::Line(line)]
+    }
+}
+
+pub fn curve_line(
+    line: Line3d,
+    prev: Option<Line3d>,
+    next: Option<Line3d>,
+    max_curve_frac: f64,
+    steps_multiplier: f64,
+) -> Vec<Line3d> {
+    fillet_line(line, prev, next, max_curve_frac, steps_multiplier)
+        .iter()
+        .flat_map(Segment3d::tessellate)
+        .collect()
+}
+
+/// Like `curve_line`, but keeping the fillets as arcs. Arcs are already curved,
+/// so are left alone, and don't affect their neighbours.
+pub fn curve_segment(
+    segment: Segment3d,
+    prev: Option<Segment3d>,
+    next: Option<Segment3d>,
+    max_curve_frac: f64,
+    steps_multiplier: f64,
+) -> Vec<Segment3d> {
+    let as_line = |s: Segment3d| match s {
+        Segment3d::Line(line) => Some(line),
+        Segment3d::Arc(_) => None,
+    };
+    match segment {
+        Segment3d::Line(line) => fillet_line(
+            line,
+            prev.and_then(as_line),
+            next.and_then(as_line),
+            max_curve_frac,
+            steps_multiplier,
+        ),
+        Segment3d::Arc(_) => vec![segment],
     }
 }
 
@@ -174,49 +207,48 @@ fn curve_layer<T>(layer: Layer<T>, max_curve_frac: f64, steps_multiplier: f64) -
 where
     T: Line + Copy,
 {
+    if layer.count() == 0 {
+        panic!("Cannot curve an empty layer");
+    }
+    let lines = layer.lines();
+    // the pieces added round corners by bevelled and rounded joins have no
+    // length, so the lines either side of them are curved towards each other
+    let mut before = vec![None; lines.len()];
+    let mut after = vec![None; lines.len()];
+    for i in 1..lines.len() {
+        before[i] = if lines[i - 1].length() > 0.0 {
+            Some(lines[i - 1])
+        } else {
+            before[i - 1]
+        };
+        let j = lines.len() - 1 - i;
+        after[j] = if lines[j + 1].length() > 0.0 {
+            Some(lines[j + 1])
+        } else {
+            after[j + 1]
+        };
+    }
     let mut new_lines = vec![];
-
-    match layer.count() {
-        0 => panic!("Cannot curve an empty layer"),
-        1 => {
-            new_lines.extend(layer.first().curve(None, None, max_curve_frac, steps_multiplier));
-        }
-        _ => {
-            let len = layer.count();
-            let lines = layer.lines();
-            new_lines.extend(
-                layer
-                    .first()
-                    .curve(None, Some(lines[1]), max_curve_frac, steps_multiplier),
-            );
-            for i in 1..len - 1 {
-                new_lines.extend(lines[i].curve(
-                    Some(lines[i - 1]),
-                    Some(lines[i + 1]),
-                    max_curve_frac,
-                    steps_multiplier,
-                ));
-            }
-            new_lines.extend(lines[len - 1].curve(
-                Some(lines[len - 2]),
-                None,
-                max_curve_frac,
-                steps_multiplier,
-            ));
-        }
-    };
+    for i in 0..lines.len() {
+        new_lines.extend(lines[i].curve(before[i], after[i], max_curve_frac, steps_multiplier));
+    }
     let fixed_lines = fix_lines(new_lines);
     Layer::<T>::new(fixed_lines)
 }
 
-pub fn curve_layers<T>(in_layers: Vec<Layer<T>>, max_curve_frac: f64, steps_multiplier: f64) -> Vec<Layer<T>>
+/// Curve every layer, using the matching entry of `max_curve_fracs` for each.
+pub fn curve_layers<T>(
+    in_layers: Vec<Layer<T>>,
+    max_curve_fracs: &[f64],
+    steps_multiplier: f64,
+) -> Vec<Layer<T>>
 where
     T: Line + Copy,
 {
     let mut curved_layers: Vec<Layer<T>> = vec![];
 
 
-    for layer in in_layers {
+    for (layer, &max_curve_frac) in in_layers.into_iter().zip(max_curve_fracs) {
         curved_layers.push(curve_layer(layer, max_curve_frac, steps_multiplier));
     }
     return curved_layers;
@@ -1,37 +1,134 @@
+use super::correspondence::FracMap;
 use super::holes::*;
 use crate::geom::*;
 use log::info;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// How long joining one pair of layers took, and how many triangles it made.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PairStats {
+    pub seconds: f64,
+    pub triangles: usize,
+}
+
+/// Joins up each pair of layers, putting the triangles into `sink` as they're
+/// made.
+/// The steps between layers a hole goes from and to, leaving at least one step
+/// of frame either side, or none if there are too few steps to fit one in.
+pub(crate) fn hole_skips(frame_factor: f64, steps: i64) -> Option<(i64, i64)> {
+    let from = ((frame_factor * steps as f64).round() as i64).max(1);
+    let until = (((1.0 - frame_factor) * steps as f64).round() as i64).min(steps - 1);
+    if until > from {
+        Some((from, until))
+    } else {
+        None
+    }
+}
+
 pub fn develop<T>(
     layers: Vec<Layer<T>>,
+    maps: &[FracMap],
     hole_options: HoleOptions,
-    init_steps: i64,
-    step_scale: f64,
+    steps: &[i64],
     extrude_dist: f64,
-) -> Vec<Tri3d>
+    smooth: bool,
+    cap: CapStyle,
+    deadline: Option<Instant>,
+    tris: &mut dyn TriSink,
+) -> Vec<PairStats>
 where
     T: Line + Copy,
 {
-    let mut tris: Vec<Tri3d> = Vec::new();
-    tris.extend(T::draw_layer(&layers[0].lines(), extrude_dist, false));
+    tris.push_all(T::draw_layer(&layers[0].lines(), extrude_dist, false, cap));
 
-    tris.extend(T::draw_layer(&layers.last().unwrap().lines(), extrude_dist, true));
-    let mut layer_steps = init_steps;
+    tris.push_all(T::draw_layer(
+        &layers.last().unwrap().lines(),
+        extrude_dist,
+        true,
+        cap,
+    ));
     let mut hole_scale = 1; //only useful if using HoleOptions::Everywhere
+    let mut pairs = vec![];
     for i in 1..layers.len() {
+        if deadline.map_or(false, |d| Instant::now() > d) {
+            info!("Ran out of time after {} layers", i - 1);
+            break;
+        }
+        let (pair_start, tris_before) = (Instant::now(), tris.count());
         let prev_layer = &layers[(i - 1) as usize];
         let curr_layer = &layers[i as usize];
         info!("Doing layer {}", i);
+        let layer_steps = steps[i - 1];
+
+        // the lines on the layers either side of this pair, matching fractions
+        // `start` and `end` of the way along this layer, to make the surface smooth
+        let map = &maps[i - 1];
+        let guides_between = |start: f64, end: f64| {
+            if smooth {
+                Some(Guides {
+                    before: if i >= 2 {
+                        let below = |f: f64| maps[i - 2].apply(map.apply(f));
+                        Some(layers[i - 2].chord(below(start), below(end)))
+                    } else {
+                        None
+                    },
+                    after: layers
+                        .get(i + 1)
+                        .map(|l| l.chord(maps[i].invert(start), maps[i].invert(end))),
+                })
+            } else {
+                None
+            }
+        };
 
-        tris.extend(
-            curr_layer
-                .first()
-                .endcap(prev_layer.first(), 0.0, layer_steps, None, true),
-        );
-        tris.extend(
-            curr_layer
-                .last()
-                .endcap(prev_layer.last(), 1.0, layer_steps, None, false),
-        );
+        // join `prev` to `next`, which is from `start` to `end` of the way along this
+        // layer, a flat piece at a time so each piece's guides are on the layers
+        let join_pieces = |prev: T, next: T, start: f64, end: f64, skips: Option<(i64, i64)>| {
+            let at = |f: f64| start + (end - start) * f;
+            let mut tris = vec![];
+            for w in prev.cuts_with(next).windows(2) {
+                tris.extend(prev.section(w[0], w[1]).join_non_parallel(
+                    next.section(w[0], w[1]),
+                    layer_steps,
+                    skips,
+                    guides_between(at(w[0]), at(w[1])),
+                    false,
+                ));
+            }
+            tris
+        };
+
+        // the same, but for the ends of the layers
+        let end_guides = |end: fn(&Layer<T>) -> T| {
+            if smooth {
+                Some(Guides {
+                    before: layers.get(i + 1).map(end),
+                    after: if i >= 2 { Some(end(&layers[i - 2])) } else { None },
+                })
+            } else {
+                None
+            }
+        };
+
+        tris.push_all(curr_layer.first().endcap(
+            prev_layer.first(),
+            0.0,
+            layer_steps,
+            None,
+            end_guides(Layer::first),
+            cap,
+            true,
+        ));
+        tris.push_all(curr_layer.last().endcap(
+            prev_layer.last(),
+            1.0,
+            layer_steps,
+            None,
+            end_guides(Layer::last),
+            cap,
+            false,
+        ));
 
         // find where the holes should go (if we're using HoleRegions::Everywhere)
         let (hole_regions, new_hole_scale) = calc_hole_regions(&hole_options, hole_scale);
@@ -52,27 +149,55 @@ where
             */
             let start_frac = (current_position) / layer_length;
             let end_frac = (current_position + line.length()) / layer_length;
-            let prev_lines = prev_layer.get_section(start_frac, end_frac);
+            let prev_lines = prev_layer.get_section(map.apply(start_frac), map.apply(end_frac));
 
             let total_prev_length: f64 = prev_lines.iter().map(|l| l.length()).sum();
+            let prev_count = prev_lines.len();
             let mut length_along_prev = 0.0;
-            for prev_line in prev_lines {
-                let new_start_frac = length_along_prev / total_prev_length;
-                let new_end_frac = (length_along_prev + prev_line.length()) / total_prev_length;
+            for (k, prev_line) in prev_lines.into_iter().enumerate() {
+                // a bevelled corner has no length, so share it out evenly
+                let (new_start_frac, new_end_frac) = if total_prev_length > 0.0 {
+                    (
+                        length_along_prev / total_prev_length,
+                        (length_along_prev + prev_line.length()) / total_prev_length,
+                    )
+                } else {
+                    (k as f64 / prev_count as f64, (k + 1) as f64 / prev_count as f64)
+                };
 
                 let new_part = line.section(new_start_frac, new_end_frac);
+                let (part_start, part_end) = (
+                    current_position / layer_length,
+                    (current_position + new_part.length()) / layer_length,
+                );
 
                 match hole_options {
                     HoleOptions::None => {
-                        tris.extend(prev_line.join_to(new_part, layer_steps));
+                        tris.push_all(join_pieces(prev_line, new_part, part_start, part_end, None));
                     }
                     HoleOptions::ParallelOnly { frame_factor } => {
-                        if prev_line.is_parallel_to(new_part) && new_part.length() > 0.1 {
-                            tris.extend(prev_line.join_to_with_hole(new_part, frame_factor, false));
+                        // the holes are cut from flat trapezia, which would leave gaps next to a
+                        // smooth surface, so the server refuses to do both
+                        if prev_line.is_parallel_to(new_part) && new_part.length() > 0.1 && !smooth {
+                            tris.push_all(prev_line.join_to_with_hole(new_part, frame_factor, false));
                         } else {
-                            tris.extend(prev_line.join_to(new_part, layer_steps));
+                            tris.push_all(join_pieces(prev_line, new_part, part_start, part_end, None));
                         }
                     }
+                    HoleOptions::Everywhere { frame_factor, .. } if new_part.length() < EPS => {
+                        // a bevelled corner, so carry on whatever the layer is doing there
+                        let frac = current_position / layer_length;
+                        let mut j = 1;
+                        while j < hole_regions.len() - 1 && frac >= hole_regions[j] {
+                            j += 1
+                        }
+                        let skips = if j % 2 == 0 {
+                            hole_skips(frame_factor, layer_steps)
+                        } else {
+                            None
+                        };
+                        tris.push_all(join_pieces(prev_line, new_part, part_start, part_end, skips));
+                    }
                     HoleOptions::Everywhere {
                         num_holes: _,
                         ratio: _,
@@ -81,6 +206,7 @@ where
                     } => {
                         let start_frac = current_position / layer_length;
                         let end_frac = (current_position + new_part.length()) / layer_length;
+                        let guides = guides_between(start_frac, end_frac);
 
                         let layer_frac_to_part_frac =
                             |layer_frac: f64| (layer_frac - start_frac) / (end_frac - start_frac);
@@ -88,10 +214,7 @@ where
                         let mut split_lines = vec![];
                         let mut endcaps_to_draw = vec![];
 
-                        let skips = Some((
-                            (frame_factor * layer_steps as f64).round() as i64,
-                            ((1.0 - frame_factor) * layer_steps as f64).round() as i64,
-                        ));
+                        let skips = hole_skips(frame_factor, layer_steps);
 
                         let mut j = 1;
                         while start_frac >= hole_regions[j] {
@@ -142,19 +265,22 @@ where
                                 new_part.section(layer_frac_to_part_frac(s), layer_frac_to_part_frac(e));
 
 
-                            tris.extend(prev.join_non_parallel(next, layer_steps, these_skips, false));
+                            tris.push_all(join_pieces(prev, next, s, e, these_skips));
                         }
 
 
-                        for (e, dir) in endcaps_to_draw {
+                        // with no room for the holes there are no sides to them either
+                        for (e, dir) in endcaps_to_draw.into_iter().filter(|_| skips.is_some()) {
                             let new_tris = new_part.endcap(
                                 prev_line,
                                 layer_frac_to_part_frac(e),
                                 layer_steps,
                                 skips,
+                                guides.map(|g| g.flipped()),
+                                CapStyle::Flat,
                                 dir,
                             );
-                            tris.extend(new_tris);
+                            tris.push_all(new_tris);
                         }
                     }
                 }
@@ -162,7 +288,10 @@ where
                 current_position += new_part.length();
             }
         }
-        layer_steps = (layer_steps as f64 * step_scale).round() as i64;
+        pairs.push(PairStats {
+            seconds: pair_start.elapsed().as_secs_f64(),
+            triangles: tris.count() - tris_before,
+        });
     }
-    return tris;
+    return pairs;
 }
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug, Serialize)]
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
 pub enum HoleOptions {
     None,
+    /// only where lines are parallel, and not with smoothing
     ParallelOnly {
         frame_factor: f64,
     },
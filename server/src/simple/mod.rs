@@ -1,11 +1,27 @@
+mod base;
+mod correspondence;
 pub mod curves;
 mod develop;
 mod holes;
+mod morph;
+mod schedule;
 mod simplify;
+mod smoothing;
+mod symmetry;
+mod thickness;
+mod transform;
 
 
+pub use base::{add_base, BaseOptions, BaseShape};
+pub use correspondence::{Correspondence, FracMap};
 pub use curves::curve_layers;
 pub use curves::curve_line;
-pub use develop::develop;
+pub use develop::{develop, PairStats};
 pub use holes::HoleOptions;
+pub use morph::morph_layers;
+pub use schedule::Schedule;
 pub use simplify::simplify;
+pub use smoothing::{smooth_layers, LayerSmoothing};
+pub use symmetry::{apply_symmetry, Symmetry};
+pub use thickness::ThicknessProfile;
+pub use transform::{transform_layers, LayerTransform};
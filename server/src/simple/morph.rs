@@ -0,0 +1,58 @@
+use super::correspondence::FracMap;
+use crate::geom::*;
+
+/// Adds `count` layers between each pair of consecutive layers, evenly spaced in
+/// z. Each is part way between the two in shape as well: the point on it for
+/// each pair of fractions that `maps` joins is interpolated between the points
+/// at those fractions of the layers either side. Returns the maps between every
+/// pair of the new layers too, which follow the same points.
+pub fn morph_layers(
+    layers: Vec<Layer<Line3d>>,
+    maps: Vec<FracMap>,
+    count: usize,
+) -> (Vec<Layer<Line3d>>, Vec<FracMap>) {
+    if layers.len() < 2 || count == 0 {
+        return (layers, maps);
+    }
+    let mut between = vec![];
+    let mut new_maps = vec![];
+    for i in 1..layers.len() {
+        let (prev, next) = (&layers[i - 1], &layers[i]);
+        // every corner of either layer and every knot of the map has to be a
+        // corner of the new ones, so they're straight in between
+        let pairs = maps[i - 1].pairs(&next.fractions(), &prev.fractions());
+        let prev_points: Vec<Point3d> = pairs.iter().map(|&(_, l)| prev.point(l)).collect();
+        let next_points: Vec<Point3d> = pairs.iter().map(|&(u, _)| next.point(u)).collect();
+
+        let mut new_layers = vec![];
+        // the fraction of each point along each layer, from `prev` up to `next`
+        let mut fracs: Vec<Vec<f64>> = vec![pairs.iter().map(|&(_, l)| l).collect()];
+        for step in 1..=count {
+            let t = step as f64 / (count + 1) as f64;
+            let points: Vec<Point3d> = prev_points
+                .iter()
+                .zip(next_points.iter())
+                .map(|(p, n)| p.scale(1.0 - t).add(n.scale(t)))
+                .collect();
+            let lines: Vec<Line3d> = points.windows(2).map(|w| Line3d::new(w[0], w[1])).collect();
+            let layer = Layer::<Line3d>::new(lines);
+            fracs.push(layer.fractions());
+            new_layers.push(layer);
+        }
+        fracs.push(pairs.iter().map(|&(u, _)| u).collect());
+        for w in fracs.windows(2) {
+            new_maps.push(FracMap::new(
+                w[1].iter().copied().zip(w[0].iter().copied()).collect(),
+            ));
+        }
+        between.push(new_layers);
+    }
+    between.push(vec![]);
+
+    let mut out = vec![];
+    for (layer, new_layers) in layers.into_iter().zip(between) {
+        out.push(layer);
+        out.extend(new_layers);
+    }
+    (out, new_maps)
+}
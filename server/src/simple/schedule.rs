@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+/// How a value (like the thickness) changes from the first layer to the last.
+#[derive(Deserialize, Debug, Serialize)]
+pub enum Schedule {
+    /// values spread evenly over the layers, and interpolated linearly between
+    Values(Vec<f64>),
+    Linear {
+        from: f64,
+        to: f64,
+    },
+    /// changes by the same factor each layer, so both ends have to be positive
+    Exponential {
+        from: f64,
+        to: f64,
+    },
+    /// changes slowly at first, then faster
+    EaseIn {
+        from: f64,
+        to: f64,
+    },
+    /// changes quickly at first, then slower
+    EaseOut {
+        from: f64,
+        to: f64,
+    },
+    /// changes slowly at both ends
+    EaseInOut {
+        from: f64,
+        to: f64,
+    },
+}
+
+impl Schedule {
+    /// Why this schedule can't be used, if it can't, which is checked before
+    /// `at` is, as it panics instead.
+    pub fn check(&self) -> Result<(), String> {
+        let ends = match *self {
+            Schedule::Values(ref values) if values.is_empty() => {
+                return Err("needs at least one value".to_string())
+            }
+            Schedule::Values(ref values) => values.clone(),
+            Schedule::Exponential { from, to } if from <= 0.0 || to <= 0.0 => {
+                return Err(format!("needs positive values, not {} and {}", from, to))
+            }
+            Schedule::Linear { from, to }
+            | Schedule::Exponential { from, to }
+            | Schedule::EaseIn { from, to }
+            | Schedule::EaseOut { from, to }
+            | Schedule::EaseInOut { from, to } => vec![from, to],
+        };
+        if ends.iter().all(|v| v.is_finite()) {
+            Ok(())
+        } else {
+            Err("can only have finite values".to_string())
+        }
+    }
+
+    /// The value `frac` of the way from the first layer to the last.
+    pub fn at(&self, frac: f64) -> f64 {
+        let t = frac.max(0.0).min(1.0);
+        let lerp = |from: f64, to: f64, t: f64| from + (to - from) * t;
+        match *self {
+            Schedule::Values(ref values) => match values.len() {
+                0 => panic!("A schedule needs at least one value"),
+                1 => values[0],
+                n => {
+                    let pos = t * (n - 1) as f64;
+                    let i = (pos.floor() as usize).min(n - 2);
+                    lerp(values[i], values[i + 1], pos - i as f64)
+                }
+            },
+            Schedule::Linear { from, to } => lerp(from, to, t),
+            Schedule::Exponential { from, to } => {
+                if from <= 0.0 || to <= 0.0 {
+                    panic!(
+                        "Exponential schedules need positive values, not {} and {}",
+                        from, to
+                    )
+                }
+                from * (to / from).powf(t)
+            }
+            Schedule::EaseIn { from, to } => lerp(from, to, t * t),
+            Schedule::EaseOut { from, to } => lerp(from, to, 1.0 - (1.0 - t) * (1.0 - t)),
+            Schedule::EaseInOut { from, to } => lerp(from, to, t * t * (3.0 - 2.0 * t)),
+        }
+    }
+
+    /// The value for each of `count` layers.
+    pub fn values(&self, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|i| {
+                self.at(if count > 1 {
+                    i as f64 / (count - 1) as f64
+                } else {
+                    0.0
+                })
+            })
+            .collect()
+    }
+}
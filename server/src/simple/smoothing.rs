@@ -0,0 +1,136 @@
+use super::correspondence::FracMap;
+use crate::geom::*;
+use serde::{Deserialize, Serialize};
+
+/// Ways to smooth out a whole layer, rather than just rounding off each corner.
+/// This is done to the layer before it's thickened, so the edges of a thick
+/// layer are smoothed in exactly the same way.
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
+pub enum LayerSmoothing {
+    /// cut each corner off `iterations` times, leaving the ends where they are
+    Chaikin { iterations: usize },
+    /// a centripetal Catmull-Rom spline through every corner, with each line
+    /// replaced by `steps` shorter ones
+    CatmullRom { steps: usize },
+}
+
+impl LayerSmoothing {
+    /// About how many lines each line of a layer becomes.
+    pub fn lines_per_line(&self) -> usize {
+        match *self {
+            LayerSmoothing::Chaikin { iterations } if iterations >= 64 => usize::MAX,
+            // each time doubles the number of lines
+            LayerSmoothing::Chaikin { iterations } => 1 << iterations,
+            LayerSmoothing::CatmullRom { steps } => steps.max(1),
+        }
+    }
+}
+
+/// Smooths every layer, or says which one (counting from 1) has no lines left
+/// once lines with no length are taken out. Also returns a map for each layer
+/// from fractions of the way along it after smoothing to where those points
+/// came from before.
+pub fn smooth_layers(
+    layers: Vec<Layer<Line3d>>,
+    smoothing: &LayerSmoothing,
+) -> Result<(Vec<Layer<Line3d>>, Vec<FracMap>), String> {
+    layers
+        .into_iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            let mut points = vec![layer.first().start()];
+            points.extend(layer.lines().iter().map(|l| l.end()));
+            // each point goes with the fraction of the original layer it's
+            // drawn from
+            let points: Vec<(Point3d, f64)> = points.into_iter().zip(layer.fractions()).collect();
+            let smoothed = match *smoothing {
+                LayerSmoothing::Chaikin { iterations } => (0..iterations).fold(points, |p, _| chaikin(&p)),
+                LayerSmoothing::CatmullRom { steps } => catmull_rom(&points, steps.max(1)),
+            };
+            let lines: Vec<Line3d> = smoothed
+                .windows(2)
+                .map(|w| Line3d::new(w[0].0, w[1].0))
+                .filter(|l| l.length() > EPS)
+                .collect();
+            if lines.is_empty() {
+                return Err(format!("Layer {} has no length left after smoothing it", i + 1));
+            }
+            let layer = Layer::new(lines);
+            let mut along = 0.0;
+            let mut knots = vec![(0.0, smoothed[0].1)];
+            for w in smoothed.windows(2) {
+                along += w[0].0.sub(w[1].0).norm();
+                knots.push((along / layer.length(), w[1].1));
+            }
+            Ok((layer, FracMap::new(knots)))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|smoothed| smoothed.into_iter().unzip())
+}
+
+fn chaikin(points: &[(Point3d, f64)]) -> Vec<(Point3d, f64)> {
+    let cut = |(a, fa): (Point3d, f64), (b, fb): (Point3d, f64), t: f64| {
+        (a.scale(1.0 - t).add(b.scale(t)), fa + (fb - fa) * t)
+    };
+    let mut out = vec![points[0]];
+    for w in points.windows(2) {
+        out.push(cut(w[0], w[1], 0.25));
+        out.push(cut(w[0], w[1], 0.75));
+    }
+    out.push(points[points.len() - 1]);
+    out
+}
+
+fn catmull_rom(points: &[(Point3d, f64)], steps: usize) -> Vec<(Point3d, f64)> {
+    let n = points.len();
+    if n < 3 {
+        return points.to_vec();
+    }
+    let p = |i: usize| points[i].0;
+    let mut out = vec![points[0]];
+    for i in 0..n - 1 {
+        // reflect the ends, so the spline carries straight on there
+        let p0 = if i == 0 {
+            p(0).scale(2.0).sub(p(1))
+        } else {
+            p(i - 1)
+        };
+        let p3 = if i + 2 == n {
+            p(n - 1).scale(2.0).sub(p(n - 2))
+        } else {
+            p(i + 2)
+        };
+        let ps = [p0, p(i), p(i + 1), p3];
+        let (from, to) = (points[i].1, points[i + 1].1);
+        for step in 1..steps {
+            let t = step as f64 / steps as f64;
+            out.push((centripetal(&ps, t), from + (to - from) * t));
+        }
+        out.push(points[i + 1]);
+    }
+    out
+}
+
+// the point `t` of the way from ps[1] to ps[2] on the centripetal Catmull-Rom
+// spline through ps, by the Barry-Goldman pyramid
+fn centripetal(ps: &[Point3d; 4], t: f64) -> Point3d {
+    // knot spacing is the square root of the distance between points, but
+    // never zero so repeated points don't divide by it
+    let gap = |a: Point3d, b: Point3d| a.sub(b).norm().sqrt().max(EPS);
+    let t0 = 0.0;
+    let t1 = t0 + gap(ps[0], ps[1]);
+    let t2 = t1 + gap(ps[1], ps[2]);
+    let t3 = t2 + gap(ps[2], ps[3]);
+    let t = t1 + (t2 - t1) * t;
+
+    let lerp = |a: Point3d, b: Point3d, from: f64, to: f64| {
+        a.scale((to - t) / (to - from))
+            .add(b.scale((t - from) / (to - from)))
+    };
+    let a1 = lerp(ps[0], ps[1], t0, t1);
+    let a2 = lerp(ps[1], ps[2], t1, t2);
+    let a3 = lerp(ps[2], ps[3], t2, t3);
+    let b1 = lerp(a1, a2, t0, t2);
+    let b2 = lerp(a2, a3, t1, t3);
+    lerp(b1, b2, t1, t2)
+}
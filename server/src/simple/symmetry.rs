@@ -0,0 +1,92 @@
+use crate::geom::*;
+use serde::{Deserialize, Serialize};
+
+/// Copies of the developed model to add around it, to make it symmetric.
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(default)]
+pub struct Symmetry {
+    /// the total number of copies to arrange evenly around the vertical axis
+    /// through `centre`
+    pub copies: usize,
+    pub centre: [f64; 2],
+    /// if set, also reflect the model in the vertical plane through `centre`
+    /// at this angle (in degrees from the x axis), before making copies
+    pub mirror: Option<f64>,
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Symmetry {
+            copies: 1,
+            centre: [0.0, 0.0],
+            mirror: None,
+        }
+    }
+}
+
+impl Symmetry {
+    /// Whether this leaves the model as it is.
+    pub fn is_none(&self) -> bool {
+        self.copies <= 1 && self.mirror.is_none()
+    }
+
+    /// How many copies of the model there are afterwards, counting reflections.
+    pub fn count(&self) -> usize {
+        self.copies.max(1) * if self.mirror.is_some() { 2 } else { 1 }
+    }
+}
+
+// rotates about the vertical axis through centre
+fn rotate(p: Point3d, centre: [f64; 2], angle: f64) -> Point3d {
+    let (s, c) = angle.sin_cos();
+    let (x, y) = (p.x - centre[0], p.y - centre[1]);
+    Point3d::new(centre[0] + x * c - y * s, centre[1] + x * s + y * c, p.z)
+}
+
+// reflects in the vertical plane through centre at angle to the x axis
+fn reflect(p: Point3d, centre: [f64; 2], angle: f64) -> Point3d {
+    let (s, c) = (2.0 * angle).sin_cos();
+    let (x, y) = (p.x - centre[0], p.y - centre[1]);
+    Point3d::new(centre[0] + x * c + y * s, centre[1] + x * s - y * c, p.z)
+}
+
+/// Reflects and/or repeats the model as described by `symmetry`, and then welds
+/// the copies together where they touch, so they make one solid.
+pub fn apply_symmetry(tris: Vec<Tri3d>, symmetry: &Symmetry) -> Vec<Tri3d> {
+    if symmetry.is_none() {
+        return tris;
+    }
+    let centre = symmetry.centre;
+    let mut base = tris;
+    if let Some(angle) = symmetry.mirror {
+        let angle = angle.to_radians();
+        let mirrored: Vec<Tri3d> = base
+            .iter()
+            .map(|t| {
+                // reflecting reverses the winding, so swap two vertices to keep the
+                // triangle facing outwards
+                Tri3d::from_sp(
+                    &Line3d::new(reflect(t.a, centre, angle), reflect(t.c, centre, angle)),
+                    &reflect(t.b, centre, angle),
+                    false,
+                )
+            })
+            .collect();
+        base.extend(mirrored);
+    }
+
+    let copies = symmetry.copies.max(1);
+    let mut out = Vec::with_capacity(base.len() * copies);
+    for k in 1..copies {
+        let angle = 2.0 * std::f64::consts::PI * k as f64 / copies as f64;
+        out.extend(base.iter().map(|t| {
+            Tri3d::from_sp(
+                &Line3d::new(rotate(t.a, centre, angle), rotate(t.b, centre, angle)),
+                &rotate(t.c, centre, angle),
+                false,
+            )
+        }));
+    }
+    out.extend(base);
+    weld(out, 1e-6)
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// How the thickness of each layer changes along it, as control points of
+/// `[fraction along the layer, multiple of the layer's thickness]`. The
+/// thickness is interpolated linearly between them, and held level before the
+/// first and after the last; with no points it's the same all the way along.
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[serde(default)]
+pub struct ThicknessProfile {
+    pub points: Vec<[f64; 2]>,
+}
+
+impl ThicknessProfile {
+    /// The fractions along the layer of the control points, where the thickness
+    /// can change how fast it's changing.
+    pub fn fractions(&self) -> Vec<f64> {
+        self.points.iter().map(|p| p[0]).collect()
+    }
+
+    /// The multiple of the layer's thickness `frac` of the way along it.
+    pub fn scale(&self, frac: f64) -> f64 {
+        let mut points = self.points.clone();
+        points.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+        let scale = match points.iter().position(|p| p[0] > frac) {
+            _ if points.is_empty() => 1.0,
+            Some(0) => points[0][1],
+            None => points[points.len() - 1][1],
+            Some(i) => {
+                let ([f0, s0], [f1, s1]) = (points[i - 1], points[i]);
+                s0 + (s1 - s0) * (frac - f0) / (f1 - f0)
+            }
+        };
+        scale.max(0.0)
+    }
+}
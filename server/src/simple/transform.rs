@@ -0,0 +1,81 @@
+use crate::geom::*;
+use serde::{Deserialize, Serialize};
+
+/// How each layer is moved relative to the one before it, so that the model
+/// twists, tapers or leans as it goes up. Everything happens in the xy plane, so
+/// layers stay flat.
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(default)]
+pub struct LayerTransform {
+    /// rotation per layer, in degrees anticlockwise
+    pub twist: f64,
+    /// the point layers are rotated and scaled about
+    pub centre: [f64; 2],
+    /// how much bigger each layer is than the one before
+    pub scale: f64,
+    /// how far each layer is shifted from the one before
+    pub offset: [f64; 2],
+}
+
+impl Default for LayerTransform {
+    fn default() -> Self {
+        LayerTransform {
+            twist: 0.0,
+            centre: [0.0, 0.0],
+            scale: 1.0,
+            offset: [0.0, 0.0],
+        }
+    }
+}
+
+impl LayerTransform {
+    pub fn is_identity(&self) -> bool {
+        self.twist == 0.0 && self.scale == 1.0 && self.offset == [0.0, 0.0]
+    }
+
+    /// Move a point on the layer `position` layers up from the first (which
+    /// needn't be a whole number).
+    fn apply(&self, point: Point3d, position: f64) -> Point3d {
+        let (s, c) = (self.twist * position).to_radians().sin_cos();
+        let scale = self.scale.powf(position);
+        let x = (point.x - self.centre[0]) * scale;
+        let y = (point.y - self.centre[1]) * scale;
+        Point3d::new(
+            self.centre[0] + x * c - y * s + self.offset[0] * position,
+            self.centre[1] + x * s + y * c + self.offset[1] * position,
+            point.z,
+        )
+    }
+}
+
+/// Apply `transform` to every layer, where there are `per_iteration` layers for
+/// each iteration of the fractal (i.e. one more than the number of intermediate
+/// layers), so those in between get a proportional part of it.
+pub fn transform_layers(
+    layers: Vec<Layer<Line3d>>,
+    transform: &LayerTransform,
+    per_iteration: usize,
+) -> Vec<Layer<Line3d>> {
+    if transform.is_identity() {
+        return layers;
+    }
+    layers
+        .into_iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            let position = i as f64 / per_iteration as f64;
+            Layer::<Line3d>::new(
+                layer
+                    .lines()
+                    .iter()
+                    .map(|line| {
+                        Line3d::new(
+                            transform.apply(line.start(), position),
+                            transform.apply(line.end(), position),
+                        )
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
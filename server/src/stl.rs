@@ -0,0 +1,72 @@
+//! Writes binary STL files a triangle at a time, as they're generated.
+
+use crate::geom::{Tri3d, TriSink};
+use std::io::{self, Seek, SeekFrom, Write};
+
+const HEADER: [u8; 80] = [0; 80];
+
+/// The number of triangles comes before them in the file, so a placeholder is
+/// written first and filled in by `finish`.
+pub struct StlWriter<W: Write + Seek> {
+    out: W,
+    count: usize,
+    // the first thing to go wrong, since `push` can't return it
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Seek> StlWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(&HEADER)?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(StlWriter {
+            out,
+            count: 0,
+            error: None,
+        })
+    }
+
+    /// Fills in the number of triangles, and gives back the file.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.count > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Too many triangles for an STL file",
+            ));
+        }
+        self.out.seek(SeekFrom::Start(HEADER.len() as u64))?;
+        self.out.write_all(&(self.count as u32).to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, tri: &Tri3d) -> io::Result<()> {
+        let mut bytes = [0u8; 50];
+        for (i, p) in [tri.n, tri.a, tri.b, tri.c].iter().enumerate() {
+            for (j, v) in [p.x, p.y, p.z].iter().enumerate() {
+                let at = (i * 3 + j) * 4;
+                bytes[at..at + 4].copy_from_slice(&(*v as f32).to_le_bytes());
+            }
+        }
+        // the last two bytes are the unused attribute count
+        self.out.write_all(&bytes)
+    }
+}
+
+impl<W: Write + Seek> TriSink for StlWriter<W> {
+    fn push(&mut self, tri: Tri3d) {
+        if self.error.is_none() {
+            match self.write(&tri) {
+                Ok(()) => self.count += 1,
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+}
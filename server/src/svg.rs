@@ -0,0 +1,123 @@
+//! Draws layer outlines as SVG, with each layer in its own group.
+
+use crate::geom::Point2d;
+use crate::outline::{LayerOutline, Part, Path};
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+const MARGIN: f64 = 0.05;
+const STYLE: &str = "path { fill: none; stroke-width: 1px; vector-effect: non-scaling-stroke; } \
+                     .centre { stroke: #888888; } .outer, .inner { stroke: #1f77b4; } \
+                     .curved-centre { stroke: #ff7f0e; } .curved-outer, .curved-inner { stroke: #d62728; }";
+
+// numbers are written to the nearest nanometre, if the units are mm
+fn num(x: f64) -> String {
+    let s = format!("{:.6}", x);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+fn rotate(p: Point2d, centre: Point2d, angle: f64) -> Point2d {
+    let (s, c) = angle.sin_cos();
+    let v = p.sub(centre);
+    Point2d::new(centre.x + v.x * c - v.y * s, centre.y + v.x * s + v.y * c)
+}
+
+fn path_data(path: &Path) -> String {
+    let mut d = format!("M{} {}", num(path.start.x), num(path.start.y));
+    let mut at = path.start;
+    for part in &path.parts {
+        match *part {
+            Part::Line(end) => write!(d, " L{} {}", num(end.x), num(end.y)).unwrap(),
+            Part::Arc { end, centre, sweep } => {
+                let radius = num(at.sub(centre).norm());
+                let flag = if sweep > 0.0 { 1 } else { 0 };
+                // an SVG arc can't go all the way round, so long ones are halved
+                if sweep.abs() > PI {
+                    let middle = rotate(at, centre, sweep / 2.0);
+                    write!(
+                        d,
+                        " A{} {} 0 0 {} {} {}",
+                        radius,
+                        radius,
+                        flag,
+                        num(middle.x),
+                        num(middle.y)
+                    )
+                    .unwrap();
+                }
+                write!(
+                    d,
+                    " A{} {} 0 0 {} {} {}",
+                    radius,
+                    radius,
+                    flag,
+                    num(end.x),
+                    num(end.y)
+                )
+                .unwrap();
+            }
+        }
+        at = part.end();
+    }
+    d
+}
+
+/// An SVG of the outlines, with a group for each layer (from the top) that
+/// has its height in `data-z`. The y axis goes up, as it does in the model,
+/// and each unit is a millimetre, as slicers take them to be in STL files.
+pub fn layers_svg(layers: &[(usize, LayerOutline)]) -> String {
+    let points = || {
+        layers.iter().flat_map(|(_, l)| {
+            l.paths
+                .iter()
+                .flat_map(|(_, paths)| paths.iter().flat_map(|p| p.points()))
+        })
+    };
+    let (mut min, mut max) = (
+        Point2d::new(f64::INFINITY, f64::INFINITY),
+        Point2d::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+    );
+    for p in points() {
+        min = Point2d::new(min.x.min(p.x), min.y.min(p.y));
+        max = Point2d::new(max.x.max(p.x), max.y.max(p.y));
+    }
+    if min.x > max.x {
+        min = Point2d::new(0.0, 0.0);
+        max = Point2d::new(1.0, 1.0);
+    }
+    let margin = MARGIN * (max.x - min.x).max(max.y - min.y);
+    let (width, height) = (max.x - min.x + 2.0 * margin, max.y - min.y + 2.0 * margin);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}mm" height="{}mm">"#,
+        num(min.x - margin),
+        num(-max.y - margin),
+        num(width),
+        num(height),
+        num(width),
+        num(height)
+    )
+    .unwrap();
+    writeln!(svg, "<style>{}</style>", STYLE).unwrap();
+    writeln!(svg, r#"<g transform="scale(1, -1)">"#).unwrap();
+    for (index, layer) in layers {
+        writeln!(svg, r#"<g id="layer-{}" data-z="{}">"#, index, num(layer.z)).unwrap();
+        for (kind, paths) in &layer.paths {
+            if paths.is_empty() {
+                continue;
+            }
+            let d: Vec<String> = paths.iter().map(path_data).collect();
+            writeln!(svg, r#"<path class="{}" d="{}"/>"#, kind.name(), d.join(" ")).unwrap();
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+    writeln!(svg, "</g>\n</svg>").unwrap();
+    svg
+}
@@ -0,0 +1,171 @@
+//! Records what happens while generating each mesh, so slow designs can be
+//! understood: the totals per request in a response header, with the time for
+//! each pair of layers as well in archives, and added up over all requests for
+//! `/metrics`.
+
+use crate::simple::PairStats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The breakdown of one generation.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Report {
+    /// seconds spent in each stage, in the order they ran
+    pub stages: Vec<(String, f64)>,
+    pub input_lines: usize,
+    pub simplified_lines: usize,
+    /// the lines in the layers that were joined up, after thickening or curving
+    pub developed_lines: usize,
+    /// one for each pair of layers, starting from the bottom
+    pub layer_pairs: Vec<PairStats>,
+    pub triangles: usize,
+}
+
+impl Report {
+    /// Runs `stage`, adding how long it took under `name`.
+    pub fn time<T, F>(&mut self, name: &str, stage: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let out = stage();
+        self.stages
+            .push((name.to_string(), start.elapsed().as_secs_f64()));
+        out
+    }
+
+    pub fn total_seconds(&self) -> f64 {
+        self.stages.iter().map(|(_, s)| s).sum()
+    }
+
+    pub fn slowest_pair_seconds(&self) -> f64 {
+        self.layer_pairs.iter().map(|p| p.seconds).fold(0.0, f64::max)
+    }
+
+    /// Everything but the breakdown for each pair of layers, which can be too
+    /// long for a header.
+    pub fn summary(&self) -> Summary<'_> {
+        Summary {
+            stages: &self.stages,
+            input_lines: self.input_lines,
+            simplified_lines: self.simplified_lines,
+            developed_lines: self.developed_lines,
+            layer_pairs: self.layer_pairs.len(),
+            slowest_pair_seconds: self.slowest_pair_seconds(),
+            triangles: self.triangles,
+        }
+    }
+}
+
+/// The totals from a `Report`, which stay the same size however many layers
+/// there are.
+#[derive(Serialize, Debug)]
+pub struct Summary<'a> {
+    pub stages: &'a [(String, f64)],
+    pub input_lines: usize,
+    pub simplified_lines: usize,
+    pub developed_lines: usize,
+    /// how many pairs of layers were joined
+    pub layer_pairs: usize,
+    pub slowest_pair_seconds: f64,
+    pub triangles: usize,
+}
+
+#[derive(Default)]
+struct Totals {
+    generations: u64,
+    cache_hits: u64,
+    input_lines: u64,
+    triangles: u64,
+    stage_seconds: BTreeMap<String, f64>,
+    slowest_seconds: f64,
+    slowest_pair_seconds: f64,
+}
+
+/// Totals over every generation since the server started.
+#[derive(Default)]
+pub struct Metrics {
+    totals: Mutex<Totals>,
+}
+
+impl Metrics {
+    pub fn record(&self, report: &Report) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.generations += 1;
+        totals.input_lines += report.input_lines as u64;
+        totals.triangles += report.triangles as u64;
+        for (stage, seconds) in &report.stages {
+            *totals.stage_seconds.entry(stage.clone()).or_insert(0.0) += seconds;
+        }
+        totals.slowest_seconds = totals.slowest_seconds.max(report.total_seconds());
+        totals.slowest_pair_seconds = totals.slowest_pair_seconds.max(report.slowest_pair_seconds());
+    }
+
+    /// Counts a request answered from the cache, which took no generating.
+    pub fn record_hit(&self) {
+        self.totals.lock().unwrap().cache_hits += 1;
+    }
+
+    /// The totals in Prometheus' text format.
+    pub fn render(&self) -> String {
+        let totals = self.totals.lock().unwrap();
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, values: Vec<(String, f64)>| {
+            writeln!(out, "# HELP fractal_{} {}", name, help).unwrap();
+            writeln!(out, "# TYPE fractal_{} {}", name, kind).unwrap();
+            for (labels, value) in values {
+                writeln!(out, "fractal_{}{} {}", name, labels, value).unwrap();
+            }
+        };
+        metric(
+            "generations_total",
+            "counter",
+            "Meshes generated.",
+            vec![(String::new(), totals.generations as f64)],
+        );
+        metric(
+            "cache_hits_total",
+            "counter",
+            "Requests answered from the cache.",
+            vec![(String::new(), totals.cache_hits as f64)],
+        );
+        metric(
+            "input_lines_total",
+            "counter",
+            "Lines sent to be generated.",
+            vec![(String::new(), totals.input_lines as f64)],
+        );
+        metric(
+            "triangles_total",
+            "counter",
+            "Triangles generated.",
+            vec![(String::new(), totals.triangles as f64)],
+        );
+        metric(
+            "stage_seconds_total",
+            "counter",
+            "Time spent in each stage of generation.",
+            totals
+                .stage_seconds
+                .iter()
+                .map(|(stage, seconds)| (format!("{{stage=\"{}\"}}", stage), *seconds))
+                .collect(),
+        );
+        metric(
+            "slowest_generation_seconds",
+            "gauge",
+            "The longest any one generation has taken.",
+            vec![(String::new(), totals.slowest_seconds)],
+        );
+        metric(
+            "slowest_layer_pair_seconds",
+            "gauge",
+            "The longest joining any one pair of layers has taken.",
+            vec![(String::new(), totals.slowest_pair_seconds)],
+        );
+        out
+    }
+}
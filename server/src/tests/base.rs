@@ -0,0 +1,92 @@
+//! Plates added under the model.
+
+use super::fractals::KOCH;
+use super::weld::{closed, volume};
+use crate::geom::*;
+use crate::limits::Limits;
+use crate::simple::{add_base, BaseOptions, BaseShape};
+use crate::{create_triangles, Params};
+use serde_json::json;
+
+// a unit cube sitting on the ground
+fn cube() -> Vec<Tri3d> {
+    let p = Point3d::new;
+    let tri = |a: Point3d, b: Point3d, c: Point3d| Tri3d::from_sp(&Line3d::new(a, b), &c, false);
+    let faces = [
+        [p(0., 0., 0.), p(0., 1., 0.), p(1., 1., 0.), p(1., 0., 0.)],
+        [p(0., 0., 1.), p(1., 0., 1.), p(1., 1., 1.), p(0., 1., 1.)],
+        [p(0., 0., 0.), p(1., 0., 0.), p(1., 0., 1.), p(0., 0., 1.)],
+        [p(1., 0., 0.), p(1., 1., 0.), p(1., 1., 1.), p(1., 0., 1.)],
+        [p(1., 1., 0.), p(0., 1., 0.), p(0., 1., 1.), p(1., 1., 1.)],
+        [p(0., 1., 0.), p(0., 0., 0.), p(0., 0., 1.), p(0., 1., 1.)],
+    ];
+    faces
+        .iter()
+        .flat_map(|&[a, b, c, d]| vec![tri(a, b, c), tri(a, c, d)])
+        .collect()
+}
+
+#[test]
+fn plate_is_joined_on() {
+    let options = BaseOptions {
+        shape: BaseShape::Rectangle,
+        thickness: 0.5,
+        margin: 1.0,
+    };
+    let tris = add_base(cube(), &options, None).unwrap();
+    assert!(closed(&tris));
+    // the cube, and a 3 by 3 plate under it
+    assert!(
+        (volume(&tris) - (1.0 + 9.0 * 0.5)).abs() < 1e-9,
+        "{}",
+        volume(&tris)
+    );
+}
+
+#[test]
+fn thin_models_have_no_bottom() {
+    let options = BaseOptions {
+        shape: BaseShape::Circle,
+        thickness: 0.5,
+        margin: 1.0,
+    };
+    let sides: Vec<Tri3d> = cube().into_iter().filter(|t| t.n.z == 0.0).collect();
+    assert!(add_base(sides, &options, None).is_err());
+}
+
+#[test]
+fn square_hole() {
+    let p = Point2d::new;
+    let outer = vec![p(-1., -1.), p(2., -1.), p(2., 2.), p(-1., 2.)];
+    let hole = vec![p(0., 0.), p(0., 1.), p(1., 1.), p(1., 0.)];
+    let tris = triangulate_polygon(&outer, &[hole], None).unwrap();
+    let area: f64 = tris
+        .iter()
+        .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)) / 2.0)
+        .sum();
+    assert_eq!(tris.len(), 8);
+    assert!((area - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn under_fractals() {
+    let params = Params {
+        thicken: true,
+        ..Params::default()
+    };
+    for shape in &["Rectangle", "Circle", "ConvexHull"] {
+        let data = json!({
+            "layers": KOCH.layers(3, 0.5),
+            "holes": "None",
+            "base": {"shape": shape, "margin": 0.1, "thickness": 0.1},
+        });
+        let mut tris = vec![];
+        create_triangles(
+            &serde_json::from_value(data).unwrap(),
+            &params,
+            &Limits::default(),
+            &mut tris,
+        )
+        .unwrap();
+    }
+}
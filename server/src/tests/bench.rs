@@ -0,0 +1,171 @@
+//! Times each stage of generating a mesh, for a few fractals at several depths.
+//! This is ignored by a normal `cargo test`, so run it with
+//!
+//!     cargo test --release bench -- --ignored --nocapture
+//!
+//! The timings are written to `target/bench/$BENCH_NAME.json` (`latest` if it
+//! isn't set), and if `BENCH_BASELINE` is the name of an earlier run, each time
+//! is compared with that. `BENCH_REPEATS` sets how many times each stage is run.
+
+use super::fractals::*;
+use crate::geom::*;
+use crate::simple::*;
+use crate::stl::StlWriter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const THICKNESS: f64 = 0.03;
+const CURVE_FRAC: f64 = 0.3;
+const CURVE_STEPS_MULT: f64 = 7.0;
+const STEPS: i64 = 6;
+
+/// The median and fastest of the times for one stage, in milliseconds.
+#[derive(Serialize, Deserialize, Debug)]
+struct Timing {
+    median: f64,
+    fastest: f64,
+}
+
+// runs `stage` on a fresh copy of `input` each time, only timing the stage
+fn time<I, O, F>(input: &I, repeats: usize, stage: F) -> (Timing, O)
+where
+    I: Clone,
+    F: Fn(I) -> O,
+{
+    let mut times = vec![];
+    let mut output = None;
+    for _ in 0..repeats {
+        let input = input.clone();
+        let start = Instant::now();
+        output = Some(stage(input));
+        times.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let timing = Timing {
+        median: times[times.len() / 2],
+        fastest: times[0],
+    };
+    (timing, output.unwrap())
+}
+
+fn to_binary_stl(tris: Vec<Tri3d>) -> Vec<u8> {
+    let mut writer = StlWriter::new(Cursor::new(vec![])).unwrap();
+    writer.push_all(tris);
+    writer.finish().unwrap().into_inner()
+}
+
+// each stage in the order `create_triangles` does them
+fn run(fractal: &LSystem, iterations: usize, repeats: usize) -> Vec<(&'static str, Timing)> {
+    let layers: Vec<Layer<Line3d>> = fractal
+        .layers(iterations, 0.5)
+        .iter()
+        .map(|l| {
+            Layer::new(
+                l.iter()
+                    .map(|p| Line3d::new(Point3d::new(p[0], p[1], p[2]), Point3d::new(p[3], p[4], p[5])))
+                    .collect(),
+            )
+        })
+        .collect();
+    let count = layers.len();
+    let maps = vec![FracMap::identity(); count];
+    let steps = vec![STEPS; count];
+    let curve_fracs = vec![CURVE_FRAC; count];
+
+    let (simplify_time, layers) = time(&layers, repeats, simplify);
+    let (thicken_time, thickened) = time(&layers, repeats, |layers| {
+        layers
+            .iter()
+            .map(|l| l.thicken(|_| THICKNESS, JoinStyle::default()))
+            .collect::<Vec<_>>()
+    });
+    let (curve_time, curved) = time(&thickened, repeats, |layers| {
+        curve_layers(layers, &curve_fracs, CURVE_STEPS_MULT)
+    });
+    let (develop_time, tris) = time(&curved, repeats, |layers| {
+        let mut tris = vec![];
+        develop(
+            layers,
+            &maps,
+            HoleOptions::None,
+            &steps,
+            0.0,
+            false,
+            CapStyle::Flat,
+            None,
+            &mut tris,
+        );
+        tris
+    });
+    let (stl_time, _) = time(&tris, repeats, to_binary_stl);
+    vec![
+        ("simplify", simplify_time),
+        ("thicken", thicken_time),
+        ("curve", curve_time),
+        ("develop", develop_time),
+        ("stl", stl_time),
+    ]
+}
+
+fn results_path(name: &str) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "target",
+        "bench",
+        &format!("{}.json", name),
+    ]
+    .iter()
+    .collect()
+}
+
+#[test]
+#[ignore]
+fn bench() {
+    let repeats = env::var("BENCH_REPEATS")
+        .ok()
+        .and_then(|r| r.parse().ok())
+        .unwrap_or(5);
+    let name = env::var("BENCH_NAME").unwrap_or_else(|_| "latest".to_string());
+    let baseline: Option<BTreeMap<String, BTreeMap<String, Timing>>> = env::var("BENCH_BASELINE")
+        .ok()
+        .map(|b| serde_json::from_str(&fs::read_to_string(results_path(&b)).unwrap()).unwrap());
+
+    let cases = [
+        ("koch", KOCH, vec![3, 4, 5]),
+        ("dragon", DRAGON, vec![6, 8, 10]),
+        ("hilbert", HILBERT, vec![3, 4, 5]),
+    ];
+    // sorted, so the files can be compared
+    let mut results: BTreeMap<String, BTreeMap<String, Timing>> = BTreeMap::new();
+    for (fractal_name, fractal, depths) in &cases {
+        for &iterations in depths {
+            let case = format!("{} {}", fractal_name, iterations);
+            for (stage, timing) in run(fractal, iterations, repeats) {
+                let change = baseline
+                    .as_ref()
+                    .and_then(|b| b.get(&case))
+                    .and_then(|b| b.get(stage))
+                    .map(|old| format!(" ({:+.1}%)", (timing.median / old.median - 1.0) * 100.0))
+                    .unwrap_or_default();
+                println!(
+                    "{:<12} {:<10} {:>10.2}ms median {:>10.2}ms fastest{}",
+                    case, stage, timing.median, timing.fastest, change
+                );
+                results
+                    .entry(case.clone())
+                    .or_default()
+                    .insert(stage.to_string(), timing);
+            }
+        }
+    }
+
+    let path = results_path(&name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, serde_json::to_string_pretty(&results).unwrap()).unwrap();
+    println!("Saved to {}", path.display());
+}
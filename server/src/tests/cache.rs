@@ -0,0 +1,86 @@
+//! Keeping generated files, and letting them go again.
+
+use crate::cache::{self, Cache};
+use crate::config::Config;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+// an empty directory to cache into, just for `name`
+fn dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("fractal-cache-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn keys_are_sha256() {
+    let key = cache::key(&("koch", 3));
+    assert_eq!(key.len(), 64);
+    assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(key, cache::key(&("koch", 3)));
+    assert_ne!(key, cache::key(&("koch", 4)));
+}
+
+#[test]
+fn the_oldest_go_first() {
+    let dir = dir("oldest");
+    let cache = Cache::new(Some(dir.clone()), 250, Duration::from_secs(3600));
+    for name in &["a", "b", "c"] {
+        cache.write(name, &[0; 100]).unwrap();
+        // so they're modified at different times
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(cache.read("a").is_none());
+    assert_eq!(cache.read("b"), Some(vec![0; 100]));
+    assert_eq!(cache.read("c"), Some(vec![0; 100]));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn nothing_is_kept_past_its_age() {
+    let dir = dir("age");
+    let cache = Cache::new(Some(dir.clone()), u64::MAX, Duration::from_secs(0));
+    thread::sleep(Duration::from_millis(20));
+    cache.write("a", b"model").unwrap();
+    assert!(cache.read("a").is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+// in one test, as tests run at the same time and would see each other's
+// environment variables
+#[test]
+fn bad_settings_are_refused() {
+    let refused = |var: &str, value: &str| {
+        env::set_var(var, value);
+        let loaded = Config::load();
+        env::remove_var(var);
+        loaded.unwrap_err()
+    };
+    assert!(refused("FRACTAL_LOG_LEVEL", "loud").contains("log_level"));
+    for (var, name) in &[
+        ("FRACTAL_LIMITS__TIMEOUT_SECONDS", "timeout_seconds"),
+        ("FRACTAL_CACHE_MAX_AGE_HOURS", "cache_max_age_hours"),
+    ] {
+        for value in &["-1.0", "nan", "inf", "1e300"] {
+            let reason = refused(var, value);
+            assert!(reason.contains(name), "{} = {}: {}", var, value, reason);
+        }
+    }
+}
+
+#[test]
+fn uncached_files_can_still_be_read() {
+    let cache = Cache::new(None, u64::MAX, Duration::from_secs(3600));
+    let entry = cache.entry("model.stl").unwrap();
+    entry.writer().unwrap().write_all(b"model").unwrap();
+    let mut file = entry.finish().unwrap();
+    let mut read = String::new();
+    file.read_to_string(&mut read).unwrap();
+    assert_eq!(read, "model");
+    assert!(cache.get("model.stl").is_none());
+}
@@ -0,0 +1,67 @@
+//! How the maps joining each layer to the one below follow the layers through
+//! the changes made to them before they're joined.
+
+use crate::geom::*;
+use crate::simple::{morph_layers, smooth_layers, Correspondence, LayerSmoothing};
+
+fn layer(points: &[(f64, f64)], z: f64) -> Layer<Line3d> {
+    Layer::new(
+        points
+            .windows(2)
+            .map(|w| Line3d::new(Point3d::new(w[0].0, w[0].1, z), Point3d::new(w[1].0, w[1].1, z)))
+            .collect(),
+    )
+}
+
+// an L, and above it the same with a bump in each line, which grew from them
+fn layers() -> (Vec<Layer<Line3d>>, Correspondence) {
+    let lower = layer(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0)], 0.0);
+    let upper = layer(&[(0.0, 0.0), (1.0, 0.5), (2.0, 0.0), (2.5, 0.5), (2.0, 1.0)], 1.0);
+    let parents = vec![vec![], vec![0, 0, 1, 1]];
+    (vec![lower, upper], Correspondence::Symbol { parents })
+}
+
+fn close(a: Point3d, b: Point3d) -> bool {
+    a.sub(b).norm() < 1e-9
+}
+
+#[test]
+fn morphed_layers_follow_the_map() {
+    let (input, correspondence) = layers();
+    let maps = correspondence.maps(&input);
+    let (morphed, maps) = morph_layers(input, maps, 1);
+    assert_eq!((morphed.len(), maps.len()), (3, 2));
+
+    // each point of the layer in between is half way between the points it's
+    // joined to either side
+    for i in 0..=40 {
+        let frac = i as f64 / 40.0;
+        let below = morphed[0].point(maps[0].apply(frac));
+        let above = morphed[2].point(maps[1].invert(frac));
+        assert!(
+            close(morphed[1].point(frac), below.add(above).scale(0.5)),
+            "at {}",
+            frac
+        );
+    }
+
+    // so the corner where the second group of lines starts is still joined to
+    // the corner of the L, all the way down
+    let corner = morphed[2].fractions()[2];
+    let joined = morphed[0].point(maps[0].apply(maps[1].apply(corner)));
+    assert!(close(joined, Point3d::new(2.0, 0.0, 0.0)), "{:?}", joined);
+}
+
+#[test]
+fn smoothing_maps_back_to_the_corners() {
+    let (input, _) = layers();
+    let upper = input[1].clone();
+    let (smoothed, moved) =
+        smooth_layers(vec![upper.clone()], &LayerSmoothing::CatmullRom { steps: 4 }).unwrap();
+
+    // the spline goes through every corner, and the map finds where
+    for (frac, line) in upper.fractions().iter().zip(upper.lines()) {
+        let at = smoothed[0].point(moved[0].invert(*frac));
+        assert!(close(at, line.start()), "{:?} isn't {:?}", at, line.start());
+    }
+}
@@ -0,0 +1,48 @@
+//! Curving the corners of layers.
+
+use crate::geom::*;
+use crate::simple::curve_layers;
+
+fn close(a: Point3d, b: Point3d) -> bool {
+    a.sub(b).norm() < 1e-9
+}
+
+// an L, thickened with `join` round its corner
+fn corner(join: JoinStyle) -> Layer<ThickLine3d> {
+    let p = |x: f64, y: f64| Point3d::new(x, y, 1.0);
+    Layer::new(vec![
+        Line3d::new(p(0.0, 0.0), p(1.0, 0.0)),
+        Line3d::new(p(1.0, 0.0), p(1.0, 1.0)),
+    ])
+    .thicken(|_| 0.1, join)
+}
+
+#[test]
+fn joined_corners_are_curved() {
+    for &join in &[JoinStyle::Bevel, JoinStyle::Round { steps: 4 }] {
+        let thick = corner(join);
+        assert!(thick.lines().iter().any(|l| l.length() == 0.0), "{:?}", join);
+        let curved = curve_layers(vec![thick], &[0.3], 7.0).remove(0);
+        let lines = curved.lines();
+
+        // the pieces round the corner are replaced by the curve, which the
+        // centre and both edges go round without a break
+        assert!(lines.iter().all(|l| l.length() > 0.0), "{:?}", join);
+        for w in lines.windows(2) {
+            assert!(close(w[0].end(), w[1].start()), "{:?}", join);
+            assert!(close(w[0].outer().end(), w[1].outer().start()), "{:?}", join);
+            assert!(close(w[0].inner().end(), w[1].inner().start()), "{:?}", join);
+        }
+        let corner = Point3d::new(1.0, 0.0, 1.0);
+        let nearest = lines
+            .iter()
+            .map(|l| l.start().sub(corner).norm())
+            .fold(f64::INFINITY, f64::min);
+        assert!(
+            nearest > 0.05,
+            "{:?} still goes {} from the corner",
+            join,
+            nearest
+        );
+    }
+}
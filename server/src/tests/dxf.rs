@@ -0,0 +1,142 @@
+//! The DXF outlines are written as, read back in.
+
+use crate::dxf::layers_dxf;
+use crate::geom::Point2d;
+use crate::outline::{Kind, LayerOutline, Part, Path};
+use std::f64::consts::PI;
+
+#[derive(Debug, Default)]
+struct Polyline {
+    layer: String,
+    closed: bool,
+    // x, y, z and bulge
+    vertices: Vec<[f64; 4]>,
+}
+
+// the layer names from the table, and the polylines from the entities
+fn read(dxf: &str) -> (Vec<String>, Vec<Polyline>) {
+    let lines: Vec<&str> = dxf.lines().collect();
+    assert_eq!(lines.len() % 2, 0);
+    let groups: Vec<(u32, &str)> = lines.chunks(2).map(|g| (g[0].parse().unwrap(), g[1])).collect();
+    assert_eq!(groups[groups.len() - 1], (0, "EOF"));
+
+    let (mut names, mut polylines) = (vec![], vec![]);
+    let mut entity = "";
+    for (code, value) in groups {
+        let number = || value.parse::<f64>().unwrap();
+        match (entity, code) {
+            (_, 0) => {
+                entity = value;
+                match value {
+                    "POLYLINE" => polylines.push(Polyline::default()),
+                    "VERTEX" => polylines.last_mut().unwrap().vertices.push([0.0; 4]),
+                    _ => {}
+                }
+            }
+            ("LAYER", 2) => names.push(value.to_string()),
+            ("POLYLINE", 8) => polylines.last_mut().unwrap().layer = value.to_string(),
+            ("POLYLINE", 70) => polylines.last_mut().unwrap().closed = value == "1",
+            ("VERTEX", 10) | ("VERTEX", 20) | ("VERTEX", 30) | ("VERTEX", 42) => {
+                let vertex = polylines.last_mut().unwrap().vertices.last_mut().unwrap();
+                vertex[match code {
+                    10 => 0,
+                    20 => 1,
+                    30 => 2,
+                    _ => 3,
+                }] = number();
+            }
+            _ => {}
+        }
+    }
+    (names, polylines)
+}
+
+fn outline(z: f64, start: (f64, f64), parts: Vec<Part>) -> LayerOutline {
+    LayerOutline {
+        z,
+        paths: vec![(
+            Kind::Centre,
+            vec![Path {
+                start: Point2d::new(start.0, start.1),
+                parts,
+            }],
+        )],
+    }
+}
+
+fn line(x: f64, y: f64) -> Part {
+    Part::Line(Point2d::new(x, y))
+}
+
+fn arc(end: (f64, f64), sweep: f64) -> Part {
+    Part::Arc {
+        end: Point2d::new(end.0, end.1),
+        centre: Point2d::new(0.0, 0.0),
+        sweep,
+    }
+}
+
+#[test]
+fn bulges_follow_the_arcs() {
+    let layers = vec![
+        (
+            1,
+            outline(0.5, (2.0, 0.0), vec![line(1.0, 0.0), arc((0.0, 1.0), PI / 2.0)]),
+        ),
+        (2, outline(1.0, (0.0, 1.0), vec![arc((1.0, 0.0), -PI / 2.0)])),
+    ];
+    let (names, polylines) = read(&layers_dxf(&layers, 1));
+    assert_eq!(names, vec!["ITERATION_1", "ITERATION_2"]);
+    assert_eq!(polylines.len(), 2);
+
+    let (first, second) = (&polylines[0], &polylines[1]);
+    assert_eq!((first.layer.as_str(), first.closed), ("ITERATION_1", false));
+    // the bulge is on the vertex the arc starts from, positive going anticlockwise
+    let bulge = (PI / 8.0).tan();
+    assert_eq!(first.vertices.len(), 3);
+    assert_eq!(first.vertices[0], [2.0, 0.0, 0.5, 0.0]);
+    assert!((first.vertices[1][3] - bulge).abs() < 1e-9);
+    assert_eq!(&first.vertices[2][..3], &[0.0, 1.0, 0.5]);
+
+    assert_eq!(second.layer, "ITERATION_2");
+    assert!((second.vertices[0][3] + bulge).abs() < 1e-9);
+    assert_eq!(&second.vertices[1][..3], &[1.0, 0.0, 1.0]);
+}
+
+#[test]
+fn closed_paths_dont_repeat_the_start() {
+    let square = vec![line(1.0, 0.0), line(1.0, 1.0), line(0.0, 1.0), line(0.0, 0.0)];
+    let (_, polylines) = read(&layers_dxf(&[(1, outline(0.0, (0.0, 0.0), square))], 1));
+    assert!(polylines[0].closed);
+    let corners: Vec<[f64; 2]> = polylines[0].vertices.iter().map(|v| [v[0], v[1]]).collect();
+    assert_eq!(corners, vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+
+    // a closed circle keeps the bulge on its last vertex to get back round
+    let circle = vec![arc((-1.0, 0.0), PI), arc((1.0, 0.0), PI)];
+    let (_, polylines) = read(&layers_dxf(&[(1, outline(0.0, (1.0, 0.0), circle))], 1));
+    assert!(polylines[0].closed);
+    assert_eq!(polylines[0].vertices.len(), 2);
+    assert!(polylines[0].vertices.iter().all(|v| (v[3] - 1.0).abs() < 1e-9));
+}
+
+#[test]
+fn layers_in_between_are_named_after_the_iteration_before() {
+    let layers: Vec<(usize, LayerOutline)> = (1..=7)
+        .map(|i| (i, outline(i as f64, (0.0, 0.0), vec![line(1.0, 0.0)])))
+        .collect();
+    let (names, polylines) = read(&layers_dxf(&layers, 3));
+    assert_eq!(
+        names,
+        vec![
+            "ITERATION_1",
+            "ITERATION_1_1",
+            "ITERATION_1_2",
+            "ITERATION_2",
+            "ITERATION_2_1",
+            "ITERATION_2_2",
+            "ITERATION_3"
+        ]
+    );
+    let layers: Vec<&str> = polylines.iter().map(|p| p.layer.as_str()).collect();
+    assert_eq!(layers, names);
+}
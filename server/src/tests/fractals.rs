@@ -0,0 +1,93 @@
+//! L-systems drawn into layers the same way as the web client does, so the
+//! server can be tested without it.
+
+/// An L-system where each symbol in `draws` is a line forwards, `+` and `-` turn
+/// left and right by `angle` degrees, and everything else does nothing.
+pub struct LSystem {
+    pub axiom: &'static str,
+    pub rules: &'static [(char, &'static str)],
+    pub draws: &'static str,
+    pub angle: f64,
+    /// how much shorter the lines get each iteration
+    pub scale: f64,
+}
+
+pub const KOCH: LSystem = LSystem {
+    axiom: "F",
+    rules: &[('F', "F+F--F+F")],
+    draws: "F",
+    angle: 60.0,
+    scale: 1.0 / 3.0,
+};
+
+pub const DRAGON: LSystem = LSystem {
+    axiom: "F",
+    rules: &[('F', "F+G"), ('G', "F-G")],
+    draws: "FG",
+    angle: 90.0,
+    scale: std::f64::consts::FRAC_1_SQRT_2,
+};
+
+pub const HILBERT: LSystem = LSystem {
+    axiom: "A",
+    rules: &[('A', "+BF-AFA-FB+"), ('B', "-AF+BFB+FA-")],
+    draws: "F",
+    angle: 90.0,
+    scale: 0.5,
+};
+
+pub const LEVY_C: LSystem = LSystem {
+    axiom: "F",
+    rules: &[('F', "+F--F+")],
+    draws: "F",
+    angle: 45.0,
+    scale: std::f64::consts::FRAC_1_SQRT_2,
+};
+
+pub const ARROWHEAD: LSystem = LSystem {
+    axiom: "A",
+    rules: &[('A', "B-A-B"), ('B', "A+B+A")],
+    draws: "AB",
+    angle: 60.0,
+    scale: 0.5,
+};
+
+impl LSystem {
+    /// The first `count` iterations (not including the axiom) as layers of lines,
+    /// with the layers getting closer together as the lines get shorter.
+    pub fn layers(&self, count: usize, layer_dist: f64) -> Vec<Vec<[f64; 6]>> {
+        let mut symbols: Vec<char> = self.axiom.chars().collect();
+        let mut layers = vec![];
+        let mut z = 0.0;
+        for i in 1..=count {
+            symbols = symbols
+                .into_iter()
+                .flat_map(|c| match self.rules.iter().find(|r| r.0 == c) {
+                    Some(rule) => rule.1.chars().collect(),
+                    None => vec![c],
+                })
+                .collect();
+            let length = self.scale.powi(i as i32 - 1);
+            z += layer_dist * length;
+
+            let (mut x, mut y, mut angle) = (0.0f64, 0.0f64, 0.0f64);
+            let mut layer = vec![];
+            for &c in &symbols {
+                match c {
+                    '+' => angle += self.angle,
+                    '-' => angle -= self.angle,
+                    c if self.draws.contains(c) => {
+                        let (s, co) = angle.to_radians().sin_cos();
+                        let (nx, ny) = (x + length * co, y + length * s);
+                        layer.push([x, y, z, nx, ny, z]);
+                        x = nx;
+                        y = ny;
+                    }
+                    _ => {}
+                }
+            }
+            layers.push(layer);
+        }
+        layers
+    }
+}
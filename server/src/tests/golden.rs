@@ -0,0 +1,346 @@
+//! Runs a set of fractals through the whole pipeline and compares a summary of
+//! each mesh with the one checked in under `tests/golden`, so changes to the
+//! geometry can't go unnoticed. After a deliberate change, update them with
+//!
+//!     UPDATE_GOLDEN=1 cargo test golden
+//!
+//! and check the differences in the summaries make sense.
+
+use super::fractals::*;
+use crate::geom::*;
+use crate::limits::Limits;
+use crate::{create_triangles, Data, Params};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+// how close vertices have to be to count as the same when hashing
+const WELD_TOLERANCE: f64 = 1e-6;
+// how much the measurements can differ (relative to the size of the model)
+const TOLERANCE: f64 = 1e-6;
+
+/// The query parameters for one run, with the holes that go in the body.
+struct Settings {
+    thicken: bool,
+    thickness: (f64, f64),
+    curve: Option<f64>,
+    extrude: Option<f64>,
+    smooth: bool,
+    holes: serde_json::Value,
+}
+
+const THIN: Settings = Settings {
+    thicken: false,
+    thickness: (0.0, 0.0),
+    curve: None,
+    extrude: None,
+    smooth: false,
+    holes: serde_json::Value::Null,
+};
+
+fn thick(top: f64, bottom: f64) -> Settings {
+    Settings {
+        thicken: true,
+        thickness: (top, bottom),
+        ..THIN
+    }
+}
+
+struct Case {
+    name: &'static str,
+    fractal: LSystem,
+    iterations: usize,
+    settings: Settings,
+}
+
+fn cases() -> Vec<Case> {
+    let everywhere =
+        json!({"Everywhere": {"num_holes": 3, "ratio": 1.0, "scaling_factor": 2, "frame_factor": 0.2}});
+    let parallel = json!({"ParallelOnly": {"frame_factor": 0.2}});
+    vec![
+        Case {
+            name: "koch_thin",
+            fractal: KOCH,
+            iterations: 4,
+            settings: THIN,
+        },
+        Case {
+            name: "koch_thick",
+            fractal: KOCH,
+            iterations: 4,
+            settings: thick(0.05, 0.02),
+        },
+        Case {
+            name: "koch_thick_curved",
+            fractal: KOCH,
+            iterations: 4,
+            settings: Settings {
+                curve: Some(0.3),
+                ..thick(0.05, 0.02)
+            },
+        },
+        Case {
+            name: "koch_thick_holes_extruded",
+            fractal: KOCH,
+            iterations: 3,
+            settings: Settings {
+                extrude: Some(0.1),
+                holes: everywhere.clone(),
+                ..thick(0.05, 0.02)
+            },
+        },
+        Case {
+            name: "koch_thin_curved_parallel_holes",
+            fractal: KOCH,
+            iterations: 3,
+            settings: Settings {
+                curve: Some(0.3),
+                holes: parallel.clone(),
+                ..THIN
+            },
+        },
+        Case {
+            name: "dragon_thin_smooth",
+            fractal: DRAGON,
+            iterations: 6,
+            settings: Settings { smooth: true, ..THIN },
+        },
+        Case {
+            name: "dragon_thick_curved_smooth",
+            fractal: DRAGON,
+            iterations: 6,
+            settings: Settings {
+                curve: Some(0.4),
+                smooth: true,
+                ..thick(0.05, 0.03)
+            },
+        },
+        Case {
+            name: "hilbert_thick",
+            fractal: HILBERT,
+            iterations: 3,
+            settings: thick(0.04, 0.04),
+        },
+        Case {
+            name: "hilbert_thick_curved_extruded",
+            fractal: HILBERT,
+            iterations: 3,
+            settings: Settings {
+                curve: Some(0.5),
+                extrude: Some(0.05),
+                ..thick(0.04, 0.02)
+            },
+        },
+        Case {
+            name: "levy_c_thin_smooth",
+            fractal: LEVY_C,
+            iterations: 6,
+            settings: Settings { smooth: true, ..THIN },
+        },
+        Case {
+            name: "levy_c_thick_parallel_holes",
+            fractal: LEVY_C,
+            iterations: 5,
+            settings: Settings {
+                holes: parallel,
+                ..thick(0.05, 0.02)
+            },
+        },
+        Case {
+            name: "arrowhead_thick_curved_extruded",
+            fractal: ARROWHEAD,
+            iterations: 4,
+            settings: Settings {
+                curve: Some(0.3),
+                extrude: Some(0.05),
+                ..thick(0.04, 0.02)
+            },
+        },
+        Case {
+            name: "arrowhead_thin_everywhere_holes",
+            fractal: ARROWHEAD,
+            iterations: 4,
+            settings: Settings {
+                holes: everywhere,
+                ..THIN
+            },
+        },
+    ]
+}
+
+fn run(case: &Case) -> Vec<Tri3d> {
+    let s = &case.settings;
+    let holes = if s.holes.is_null() {
+        json!("None")
+    } else {
+        s.holes.clone()
+    };
+    let data: Data = serde_json::from_value(json!({
+        "layers": case.fractal.layers(case.iterations, 0.5),
+        "holes": holes,
+    }))
+    .unwrap();
+    let params = Params {
+        thicken: s.thicken,
+        top_thickness: s.thickness.0,
+        bottom_thickness: s.thickness.1,
+        curve: s.curve.is_some(),
+        max_curve_frac: s.curve.unwrap_or_default(),
+        curve_steps_mult: 7.0,
+        init_steps: 6,
+        step_scale: 1.0,
+        extrude: s.extrude.is_some(),
+        extrude_dist: s.extrude.unwrap_or_default(),
+        smooth: s.smooth,
+        intermediate_layers: 0,
+    };
+    let mut tris = vec![];
+    create_triangles(&data, &params, &Limits::default(), &mut tris).unwrap();
+    tris
+}
+
+/// What's compared between runs.
+#[derive(Serialize, Deserialize, Debug)]
+struct Summary {
+    triangles: usize,
+    min: [f64; 3],
+    max: [f64; 3],
+    volume: f64,
+    /// of the welded triangles, ignoring their order
+    hash: String,
+}
+
+fn summarise(tris: &[Tri3d]) -> Summary {
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for t in tris {
+        for p in &[t.a, t.b, t.c] {
+            for (i, x) in [p.x, p.y, p.z].iter().enumerate() {
+                min[i] = min[i].min(*x);
+                max[i] = max[i].max(*x);
+            }
+        }
+    }
+    // the signed volumes of the tetrahedra from the middle, which only adds up
+    // to the real volume if the mesh is closed
+    let middle = Point3d::new(
+        (min[0] + max[0]) / 2.0,
+        (min[1] + max[1]) / 2.0,
+        (min[2] + max[2]) / 2.0,
+    );
+    let volume: f64 = tris
+        .iter()
+        .map(|t| {
+            let (a, b, c) = (t.a.sub(middle), t.b.sub(middle), t.c.sub(middle));
+            a.dot(b.cross(c)) / 6.0
+        })
+        .sum();
+
+    let key = |p: Point3d| {
+        let k = |x: f64| (x / WELD_TOLERANCE).round() as i64;
+        [k(p.x), k(p.y), k(p.z)]
+    };
+    let mut faces: Vec<[[i64; 3]; 3]> = weld(tris.to_vec(), WELD_TOLERANCE)
+        .iter()
+        .map(|t| {
+            // start from the smallest vertex, keeping the winding
+            let keys = [key(t.a), key(t.b), key(t.c)];
+            let first = (0..3).min_by_key(|&i| keys[i]).unwrap();
+            [keys[first], keys[(first + 1) % 3], keys[(first + 2) % 3]]
+        })
+        .collect();
+    faces.sort();
+    Summary {
+        triangles: tris.len(),
+        min,
+        max,
+        volume,
+        hash: format!("{:016x}", fnv1a(faces.iter().flatten().flatten())),
+    }
+}
+
+// not the standard library's hasher, as that can change between versions
+fn fnv1a<'a, I>(values: I) -> u64
+where
+    I: Iterator<Item = &'a i64>,
+{
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for v in values {
+        for byte in &v.to_le_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn differences(expected: &Summary, actual: &Summary) -> Vec<String> {
+    let size = (0..3)
+        .map(|i| expected.max[i] - expected.min[i])
+        .fold(1.0, f64::max);
+    let close = |a: f64, b: f64, scale: f64| (a - b).abs() <= TOLERANCE * scale;
+    let mut out = vec![];
+    if expected.triangles != actual.triangles {
+        out.push(format!(
+            "{} triangles, not {}",
+            actual.triangles, expected.triangles
+        ));
+    }
+    for i in 0..3 {
+        if !close(expected.min[i], actual.min[i], size) || !close(expected.max[i], actual.max[i], size) {
+            out.push(format!(
+                "bounds {:?} to {:?}, not {:?} to {:?}",
+                actual.min, actual.max, expected.min, expected.max
+            ));
+            break;
+        }
+    }
+    if !close(expected.volume, actual.volume, size.powi(3)) {
+        out.push(format!("volume {}, not {}", actual.volume, expected.volume));
+    }
+    if expected.hash != actual.hash {
+        out.push(format!("hash {}, not {}", actual.hash, expected.hash));
+    }
+    out
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "golden",
+        &format!("{}.json", name),
+    ]
+    .iter()
+    .collect()
+}
+
+#[test]
+fn golden() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = vec![];
+    for case in cases() {
+        let actual = summarise(&run(&case));
+        let path = golden_path(case.name);
+        if update {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+            continue;
+        }
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                let expected: Summary = serde_json::from_str(&text).unwrap();
+                for difference in differences(&expected, &actual) {
+                    failures.push(format!("{}: {}", case.name, difference));
+                }
+            }
+            Err(_) => failures.push(format!("{}: no golden file at {}", case.name, path.display())),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "meshes have changed (run with UPDATE_GOLDEN=1 if that's intended):\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,46 @@
+//! Holes cut through the model between layers.
+
+use super::fractals::KOCH;
+use crate::limits::Limits;
+use crate::{create_triangles, Params};
+use serde_json::{json, Value};
+
+// the koch curve with holes everywhere, and `extra` added to the body
+fn model(extra: Value, params: &Params) {
+    let mut body = json!({
+        "layers": KOCH.layers(3, 0.5),
+        "holes": {"Everywhere": {"num_holes": 3, "ratio": 1.0, "scaling_factor": 2, "frame_factor": 0.4}},
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        body[key] = value.clone();
+    }
+    let mut tris = vec![];
+    create_triangles(
+        &serde_json::from_value(body).unwrap(),
+        params,
+        &Limits::default(),
+        &mut tris,
+    )
+    .unwrap();
+    assert!(!tris.is_empty());
+}
+
+#[test]
+fn too_few_steps_for_a_hole() {
+    // there's no room for a hole inside the frame, so those parts are left solid
+    for &thicken in &[false, true] {
+        for &init_steps in &[1, 2, 3] {
+            let params = Params {
+                thicken,
+                init_steps,
+                ..Params::default()
+            };
+            model(json!({}), &params);
+        }
+        let params = Params {
+            thicken,
+            ..Params::default()
+        };
+        model(json!({"steps_schedule": {"Values": [1.0, 2.0]}}), &params);
+    }
+}
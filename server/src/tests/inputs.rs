@@ -0,0 +1,184 @@
+//! Requests that should be refused with an error saying why, before any of
+//! the model is made.
+
+use super::fractals::*;
+use crate::limits::{LimitError, Limits};
+use crate::{create_outlines, create_triangles, Data, Params};
+use serde_json::{json, Value};
+
+// the koch curve, with `extra` added to the body
+fn data(extra: Value) -> Data {
+    let mut body = json!({
+        "layers": KOCH.layers(3, 0.5),
+        "holes": "None",
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        body[key] = value.clone();
+    }
+    serde_json::from_value(body).unwrap()
+}
+
+// the errors the model and the outlines are refused with
+fn errors(extra: Value, params: &Params) -> (LimitError, LimitError) {
+    let mut tris = vec![];
+    let model = match create_triangles(&data(extra.clone()), params, &Limits::default(), &mut tris) {
+        Err(error) => error,
+        Ok(report) => panic!("expected the model to be refused, not {:?}", report),
+    };
+    assert!(tris.is_empty());
+    let outlines = match create_outlines(data(extra), params, &Limits::default()) {
+        Err(error) => error,
+        Ok(_) => panic!("expected the outlines to be refused"),
+    };
+    (model, outlines)
+}
+
+// the reason both the model and the outlines are refused with
+fn refused(extra: Value, params: &Params) -> String {
+    match errors(extra, params) {
+        (LimitError::Invalid { reason }, LimitError::Invalid { .. }) => reason,
+        other => panic!("expected both to be refused as invalid, not {:?}", other),
+    }
+}
+
+// the setting both the model and the outlines are refused for
+fn too_detailed(extra: Value, params: &Params) -> String {
+    match errors(extra, params) {
+        (LimitError::TooMuchDetail { setting, .. }, LimitError::TooMuchDetail { .. }) => setting,
+        other => panic!("expected both to be refused as too detailed, not {:?}", other),
+    }
+}
+
+#[test]
+fn smooth_parallel_holes() {
+    let params = Params {
+        smooth: true,
+        ..Params::default()
+    };
+    let reason = refused(json!({"holes": {"ParallelOnly": {"frame_factor": 0.2}}}), &params);
+    assert!(reason.contains("smoothing"));
+}
+
+#[test]
+fn base_without_margin() {
+    for (margin, thickness) in &[(0.0, 0.1), (-1.0, 0.1), (0.1, 0.0)] {
+        let base = json!({"shape": "Circle", "margin": margin, "thickness": thickness});
+        refused(json!({ "base": base }), &Params::default());
+    }
+}
+
+#[test]
+fn bad_chamfer() {
+    for size in &[0.0, -0.2, 0.6, 1e300] {
+        let cap = json!({"Chamfer": { "size": size }});
+        let reason = refused(json!({ "cap": cap }), &Params::default());
+        assert!(reason.contains("chamfer"), "{}", reason);
+    }
+}
+
+#[test]
+fn bad_scale() {
+    for scale in &[0.0, -0.5] {
+        let transform = json!({ "scale": scale });
+        let reason = refused(json!({ "transform": transform }), &Params::default());
+        assert!(reason.contains("scale"), "{}", reason);
+    }
+}
+
+#[test]
+fn base_under_thin_model() {
+    let base = json!({"shape": "Rectangle", "margin": 0.1, "thickness": 0.1});
+    let reason = refused(json!({ "base": base }), &Params::default());
+    assert!(reason.contains("thickened"));
+}
+
+#[test]
+fn bad_schedules() {
+    let reason = refused(json!({"thickness_schedule": {"Values": []}}), &Params::default());
+    assert!(reason.contains("thickness"));
+    let exponential = json!({"Exponential": {"from": 1.0, "to": 0.0}});
+    let reason = refused(json!({ "steps_schedule": exponential }), &Params::default());
+    assert!(reason.contains("positive"));
+}
+
+#[test]
+fn smoothed_to_nothing() {
+    // the top layer is shrunk to almost nothing, so its lines are too short to
+    // be kept once it's smoothed
+    let transform = json!({"scale": 1e-10});
+    let smoothing = json!({"Chaikin": {"iterations": 2}});
+    let reason = refused(
+        json!({ "layers": KOCH.layers(3, 0.5), "transform": transform, "layer_smoothing": smoothing }),
+        &Params::default(),
+    );
+    assert!(
+        reason.contains("Layer 3") && reason.contains("smoothing"),
+        "{}",
+        reason
+    );
+}
+
+#[test]
+fn too_much_detail() {
+    let params = Params {
+        intermediate_layers: Limits::default().max_intermediate_layers + 1,
+        ..Params::default()
+    };
+    assert!(too_detailed(json!({}), &params).contains("layers in between"));
+    let cases = [
+        (
+            json!({"layer_smoothing": {"Chaikin": {"iterations": 7}}}),
+            "smoothed",
+        ),
+        (
+            json!({"layer_smoothing": {"Chaikin": {"iterations": 1000}}}),
+            "smoothed",
+        ),
+        (
+            json!({"layer_smoothing": {"CatmullRom": {"steps": 65}}}),
+            "smoothed",
+        ),
+        (json!({"join": {"Round": {"steps": 65}}}), "join"),
+        (json!({"cap": {"Round": {"steps": 100000}}}), "end"),
+        (
+            json!({"symmetry": {"copies": 65, "centre": [0.0, 0.0]}}),
+            "copies",
+        ),
+    ];
+    for (extra, setting) in cases.iter() {
+        assert!(too_detailed(extra.clone(), &Params::default()).contains(setting));
+    }
+    let params = Params {
+        curve: true,
+        curve_steps_mult: 1e300,
+        ..Params::default()
+    };
+    assert!(too_detailed(json!({}), &params).contains("curved corner"));
+}
+
+#[test]
+fn bad_curve_steps() {
+    for &mult in &[0.0, -7.0, f64::NAN, f64::INFINITY] {
+        let params = Params {
+            curve: true,
+            curve_steps_mult: mult,
+            ..Params::default()
+        };
+        let reason = refused(json!({}), &params);
+        assert!(reason.contains("curves"), "{}", reason);
+    }
+}
+
+#[test]
+fn empty_layers() {
+    let mut layers = KOCH.layers(3, 0.5);
+    layers[1] = vec![];
+    let reason = refused(json!({ "layers": layers }), &Params::default());
+    assert!(reason.contains("Layer 2"));
+    // a layer of points is no better
+    let z = layers[0][0][2];
+    layers[1] = KOCH.layers(3, 0.5)[1].clone();
+    layers[0] = vec![[1.0, 1.0, z, 1.0, 1.0, z]];
+    let reason = refused(json!({ "layers": layers }), &Params::default());
+    assert!(reason.contains("Layer 1"));
+}
@@ -0,0 +1,17 @@
+mod base;
+mod bench;
+mod cache;
+mod correspondence;
+mod curves;
+mod dxf;
+mod fractals;
+mod golden;
+mod holes;
+mod inputs;
+mod paths;
+mod properties;
+mod render;
+mod stl;
+mod svg;
+mod thickness;
+mod weld;
@@ -0,0 +1,123 @@
+//! Reading layers from SVG path data.
+
+use crate::geom::Line;
+use crate::paths::{parse_path, SvgLayers};
+
+// the corners the path goes through, or why it can't be read
+fn corners(d: &str) -> Result<Vec<(f64, f64)>, String> {
+    let lines = parse_path(d, 0.0, 0.01, 1000)?;
+    let mut points = vec![(lines[0].start().x, lines[0].start().y)];
+    points.extend(lines.iter().map(|l| (l.end().x, l.end().y)));
+    Ok(points)
+}
+
+fn svg_layers(paths: &[&str], tolerance: f64) -> SvgLayers {
+    SvgLayers {
+        paths: paths.iter().map(|p| p.to_string()).collect(),
+        heights: (0..paths.len()).map(|i| i as f64).collect(),
+        tolerance,
+    }
+}
+
+#[test]
+fn relative_commands() {
+    assert_eq!(
+        corners("M1 1 l2 0 l0 2").unwrap(),
+        vec![(1.0, 1.0), (3.0, 1.0), (3.0, 3.0)]
+    );
+    assert_eq!(
+        corners("m1 1 h2 v2 h-3").unwrap(),
+        vec![(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (0.0, 3.0)]
+    );
+    // and absolute ones after them are still absolute
+    assert_eq!(
+        corners("M1 1 l1 0 L0 0 V2").unwrap(),
+        vec![(1.0, 1.0), (2.0, 1.0), (0.0, 0.0), (0.0, 2.0)]
+    );
+}
+
+#[test]
+fn implicit_repeats() {
+    // numbers after a move are lines, in the same way round as the move
+    assert_eq!(
+        corners("M0 0 1 0 1 1").unwrap(),
+        vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+    );
+    assert_eq!(
+        corners("m1 0 1 0 0 1").unwrap(),
+        vec![(1.0, 0.0), (2.0, 0.0), (2.0, 1.0)]
+    );
+    // and after anything else, the same command again
+    assert_eq!(
+        corners("M0 0 H1 2 3").unwrap(),
+        vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]
+    );
+    assert_eq!(
+        corners("M0,0L1,0,1,1").unwrap(),
+        vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+    );
+    // numbers can run into each other where there's no mistaking them
+    assert_eq!(
+        corners("M0 0L1-1.5.5-2").unwrap(),
+        vec![(0.0, 0.0), (1.0, -1.5), (0.5, -2.0)]
+    );
+}
+
+#[test]
+fn closing() {
+    assert_eq!(
+        corners("M0 0 L1 0 L1 1 Z").unwrap(),
+        vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]
+    );
+    // moves after a relative close are from the start again
+    assert_eq!(
+        corners("m1 1 l1 0 l0 1 z m0 0 l0 -1").unwrap(),
+        vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 1.0), (1.0, 0.0)]
+    );
+    assert!(corners("M0 0 L1 0 Z 1 1").unwrap_err().contains("command"));
+}
+
+#[test]
+fn malformed_numbers() {
+    for d in &["M0 0 L1e 2", "M0 0 L- 2", "M0 0 L1 0 C1 2", "M0 0 L1 ."] {
+        assert!(corners(d).unwrap_err().contains("number"), "{}", d);
+    }
+    assert!(corners("M0 0 L1e999 0").unwrap_err().contains("too big"));
+    assert!(corners("L1 1").unwrap_err().contains("move"));
+    assert!(corners("M0 0 A1 1 0 0 1 2 0").unwrap_err().contains("supported"));
+    assert!(corners("M0 0 L0 0").unwrap_err().contains("doesn't draw"));
+}
+
+#[test]
+fn curves() {
+    let flat = parse_path("M0 0 Q1 2 2 0", 0.0, 0.01, 1000).unwrap();
+    assert!(flat.len() > 2);
+    assert_eq!(
+        (flat[flat.len() - 1].end().x, flat[flat.len() - 1].end().y),
+        (2.0, 0.0)
+    );
+    // the middle of the curve is half way to the control point
+    let middle = flat.iter().map(|l| l.end().y).fold(0.0, f64::max);
+    assert!((middle - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn limits() {
+    let square = "M0 0 H1 V1 H0 Z";
+    assert_eq!(svg_layers(&[square, square], 0.01).layers(8).unwrap().len(), 2);
+    let error = svg_layers(&[square, square], 0.01).layers(7).unwrap_err();
+    assert_eq!(error.layer, 2);
+    // a curve stops being split up as soon as it's too many lines
+    let error = svg_layers(&["M0 0 C0 1000 1000 1000 1000 0"], 1e-4)
+        .layers(50)
+        .unwrap_err();
+    assert!(error.message.contains("50"));
+
+    for tolerance in &[0.0, 1e-9, -1.0, f64::NAN, f64::INFINITY] {
+        let error = svg_layers(&[square], *tolerance).layers(100).unwrap_err();
+        assert!(error.message.contains("tolerance"), "{}", tolerance);
+    }
+    let mut layers = svg_layers(&[square], 0.01);
+    layers.heights[0] = f64::INFINITY;
+    assert!(layers.layers(100).unwrap_err().message.contains("height"));
+}
@@ -0,0 +1,283 @@
+//! Property tests for the invariants the rest of the geometry relies on, checked
+//! against random polylines.
+
+use crate::geom::*;
+use proptest::prelude::*;
+use std::f64::consts::PI;
+
+const TOLERANCE: f64 = 1e-7;
+
+fn close(a: Point3d, b: Point3d) -> bool {
+    a.sub(b).norm() < TOLERANCE
+}
+
+// a polyline on z = 1 made of lines going `length` in the direction `angle`, so
+// none of them have no length
+fn polyline(steps: Vec<(f64, f64)>) -> Vec<Line3d> {
+    let mut point = Point3d::new(0.0, 0.0, 1.0);
+    steps
+        .into_iter()
+        .map(|(length, angle)| {
+            let next = point.add(Point3d::new(length * angle.cos(), length * angle.sin(), 0.0));
+            let line = Line3d::new(point, next);
+            point = next;
+            line
+        })
+        .collect()
+}
+
+fn polylines() -> impl Strategy<Value = Vec<Line3d>> {
+    prop::collection::vec((0.05..5.0, -PI..PI), 1..12).prop_map(polyline)
+}
+
+// polylines that never turn back on themselves by more than about 100°, so
+// inside corners are never too sharp to mitre
+fn gentle_polylines() -> impl Strategy<Value = Vec<Line3d>> {
+    prop::collection::vec((0.05..5.0, -1.8..1.8), 1..12).prop_map(|steps: Vec<(f64, f64)>| {
+        let mut angle = 0.0;
+        polyline(
+            steps
+                .into_iter()
+                .map(|(length, turn)| {
+                    angle += turn;
+                    (length, angle)
+                })
+                .collect(),
+        )
+    })
+}
+
+fn joins() -> impl Strategy<Value = JoinStyle> {
+    prop_oneof![
+        Just(JoinStyle::Mitre { limit: f64::INFINITY }),
+        (2.0..10.0).prop_map(|limit| JoinStyle::Mitre { limit }),
+        Just(JoinStyle::Bevel),
+        (1_i64..8).prop_map(|steps| JoinStyle::Round { steps }),
+    ]
+}
+
+fn caps() -> impl Strategy<Value = CapStyle> {
+    prop_oneof![
+        Just(CapStyle::Flat),
+        (1_i64..8).prop_map(|steps| CapStyle::Round { steps }),
+        (0.0..2.0).prop_map(|size| CapStyle::Chamfer { size }),
+    ]
+}
+
+fn finite(p: Point3d) -> bool {
+    p.x.is_finite() && p.y.is_finite() && p.z.is_finite()
+}
+
+// fractions from 0 to 1, in order
+fn cuts() -> impl Strategy<Value = Vec<f64>> {
+    prop::collection::vec(0.0..1.0, 0..8).prop_map(|mut fracs: Vec<f64>| {
+        fracs.extend(&[0.0, 1.0]);
+        fracs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        fracs.dedup_by(|a, b| *a - *b < 1e-6);
+        fracs
+    })
+}
+
+// how far `point` is to the right of the line through `line`
+fn side_distance(line: Line2d, point: Point2d) -> f64 {
+    let dir = line.end.sub(line.start).unit();
+    let v = point.sub(line.start);
+    v.x * dir.y - v.y * dir.x
+}
+
+fn line2d(start: (f64, f64), length: f64, angle: f64) -> Line2d {
+    let start = Point2d::new(start.0, start.1);
+    Line2d::new(
+        start,
+        start.add(Point2d::new(length * angle.cos(), length * angle.sin())),
+    )
+}
+
+fn vectors() -> impl Strategy<Value = Point3d> {
+    (-10.0..10.0, -10.0..10.0, -10.0..10.0).prop_map(|(x, y, z)| Point3d::new(x, y, z))
+}
+
+proptest! {
+    #[test]
+    fn cross_is_square_to_both(a in vectors(), b in vectors()) {
+        let c = a.cross(b);
+        prop_assert!(c.dot(a).abs() < TOLERANCE * a.norm() * b.norm() * a.norm());
+        prop_assert!(c.dot(b).abs() < TOLERANCE * a.norm() * b.norm() * b.norm());
+        // its length is the area of the parallelogram the two make
+        let area = (a.dot(a) * b.dot(b) - a.dot(b).powi(2)).max(0.0).sqrt();
+        prop_assert!((c.norm() - area).abs() < TOLERANCE * a.norm() * b.norm());
+        prop_assert!(close(
+            Point3d::new(1.0, 0.0, 0.0).cross(Point3d::new(0.0, 1.0, 0.0)),
+            Point3d::new(0.0, 0.0, 1.0)
+        ));
+    }
+
+    #[test]
+    fn sections_join_up_into_the_layer(lines in polylines(), fracs in cuts()) {
+        let layer = Layer::new(lines);
+        let mut total = 0.0;
+        let mut prev_end = layer.first().start();
+        for w in fracs.windows(2) {
+            let section = layer.get_section(w[0], w[1]);
+            prop_assert!(close(section[0].start(), prev_end));
+            prop_assert!(close(section[0].start(), layer.point(w[0])));
+            prop_assert!(close(section[section.len() - 1].end(), layer.point(w[1])));
+            for pair in section.windows(2) {
+                prop_assert!(close(pair[0].end(), pair[1].start()));
+            }
+            let length: f64 = section.iter().map(|l| l.length()).sum();
+            prop_assert!((length - (w[1] - w[0]) * layer.length()).abs() < TOLERANCE);
+            total += length;
+            prev_end = section[section.len() - 1].end();
+        }
+        prop_assert!(close(prev_end, layer.last().end()));
+        prop_assert!((total - layer.length()).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn line_sections_join_up(lines in polylines(), a in 0.0..1.0, b in 0.0..1.0, c in 0.0..1.0) {
+        let line = lines[0];
+        let mut fracs: [f64; 3] = [a, b, c];
+        fracs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let [s, m, e] = fracs;
+        let (first, second, whole) = (line.section(s, m), line.section(m, e), line.section(s, e));
+        prop_assert!(close(first.end(), second.start()));
+        prop_assert!(close(first.start(), whole.start()));
+        prop_assert!(close(second.end(), whole.end()));
+        prop_assert!((first.length() + second.length() - whole.length()).abs() < TOLERANCE);
+        prop_assert!((whole.length() - (e - s) * line.length()).abs() < TOLERANCE);
+        let all = line.section(0.0, 1.0);
+        prop_assert!(close(all.start(), line.start()) && close(all.end(), line.end()));
+    }
+
+    #[test]
+    fn offsets_are_parallel_and_the_right_distance_away(
+        start in (-5.0..5.0, -5.0..5.0),
+        length in 0.1..5.0,
+        angle in -PI..PI,
+        offset in -1.0..1.0,
+        turns in (-2.5..2.5, -2.5..2.5),
+        neighbours in any::<(bool, bool)>(),
+    ) {
+        let line = line2d(start, length, angle);
+        let prev = line2d(start, -1.0, angle - turns.0);
+        let prev = Line2d::new(prev.end, prev.start);
+        let next = line2d((line.end.x, line.end.y), 1.0, angle + turns.1);
+        let offset_line = offset_line2(
+            line,
+            if neighbours.0 { Some(prev) } else { None },
+            if neighbours.1 { Some(next) } else { None },
+            offset,
+        );
+        // both ends on the line `offset` to the right, whatever happens at the corners
+        prop_assert!((side_distance(line, offset_line.start) - offset).abs() < TOLERANCE);
+        prop_assert!((side_distance(line, offset_line.end) - offset).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn projecting_then_unprojecting_changes_nothing(
+        a in polylines(),
+        b in polylines(),
+        z in -5.0..5.0,
+        u in -10.0..10.0,
+        v in -10.0..10.0,
+    ) {
+        let (x_line, other) = (a[0], b[0]);
+        // tip the second line out of the z= plane so the plane is a general one
+        let y_line = Line3d::new(x_line.start(), x_line.start().add(other.direction()).add(Point3d::new(0.0, 0.0, z)));
+        prop_assume!(x_line.direction().cross(y_line.direction()).norm() > 0.1);
+        let plane = Plane3d::from_two_lines(x_line, y_line);
+
+        // `v` can be negative, which is on the other side of `x_line` to `y_line`
+        let point = plane.unproject_point(Point2d::new(u, v));
+        let projected = plane.project_point(point);
+        prop_assert!((projected.x - u).abs() < TOLERANCE && (projected.y - v).abs() < TOLERANCE);
+        prop_assert!(close(plane.unproject_point(projected), point));
+        prop_assert!(close(plane.unproject_point(plane.project_point(y_line.end())), y_line.end()));
+    }
+
+    #[test]
+    fn merging_arc_sections_gives_the_whole_arc(
+        start in -PI..PI,
+        sweep in prop_oneof![-6.0..-0.1, 0.1..6.0],
+        cut in 0.05..0.95,
+    ) {
+        let centre = Point3d::new(1.0, 2.0, 0.5);
+        let first = centre.add(Point3d::new(2.0 * start.cos(), 2.0 * start.sin(), 0.0));
+        let arc = Arc3d::new(centre, first, sweep, 10.0);
+        let (a, b) = (arc.section(0.0, cut), arc.section(cut, 1.0));
+        match Segment3d::Arc(a).merge_with_parallel(Segment3d::Arc(b)) {
+            Segment3d::Arc(merged) => {
+                prop_assert!((merged.sweep() - sweep).abs() < TOLERANCE);
+                prop_assert!(close(merged.end(), arc.end()));
+            }
+            Segment3d::Line(_) => prop_assert!(false, "merged into a line"),
+        }
+    }
+
+    #[test]
+    fn arc_steps_count_the_lines_drawn(
+        sweep in prop_oneof![-6.0..-0.1, 0.1..6.0],
+        mult in 0.1..20.0,
+        ends in (0.0..1.0, 0.0..1.0),
+    ) {
+        let centre = Point3d::new(1.0, 2.0, 0.5);
+        let arc = Arc3d::new(centre, centre.add(Point3d::new(2.0, 0.0, 0.0)), sweep, mult);
+        let (s, e): (f64, f64) = ends;
+        for part in &[arc, arc.section(s.min(e), s.max(e))] {
+            prop_assert_eq!(part.steps() as usize, part.tessellate().len());
+        }
+    }
+
+    #[test]
+    fn layer_edges_are_the_right_distance_away(
+        lines in gentle_polylines(),
+        offset in prop_oneof![-1.0..-0.01, 0.01..1.0],
+        join in joins(),
+    ) {
+        let (edges, corners) = offset_layer(&lines, &vec![offset; lines.len() + 1], join);
+        prop_assert_eq!(edges.len(), lines.len());
+        prop_assert_eq!(corners.len(), lines.len() - 1);
+        // away from the corners the edge is `offset` to the right of each line
+        for (line, edge) in lines.iter().zip(&edges) {
+            for p in &[edge.start(), edge.end()] {
+                prop_assert!(finite(*p));
+                prop_assert!((side_distance(line.to2d(), p.to2d()) - offset).abs() < TOLERANCE);
+            }
+        }
+        // and going round them it's never closer than that to the corner
+        for (line, points) in lines.iter().zip(&corners) {
+            for p in points {
+                prop_assert!(finite(*p));
+                prop_assert!(p.sub(line.end()).norm() > offset.abs() - TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn caps_go_from_edge_to_edge_past_the_end(
+        lines in polylines(),
+        thickness in 0.05..0.25,
+        cap in caps(),
+    ) {
+        // as a fraction of the shortest line, so no corner reaches the ends
+        let thickness = thickness * lines.iter().map(|l| l.length()).fold(f64::INFINITY, f64::min);
+        let layer = Layer::new(lines.clone()).thicken(|_| thickness, JoinStyle::Bevel);
+        let ends = [
+            (layer.first(), 0.0, lines[0].start(), lines[0].direction().scale(-1.0)),
+            (layer.last(), 1.0, lines[lines.len() - 1].end(), lines[lines.len() - 1].direction()),
+        ];
+        for &(line, point, end, out) in &ends {
+            let profile = line.end_profile(point, cap);
+            prop_assert!(close(profile[0], line.inner().point(point)));
+            prop_assert!(close(profile[profile.len() - 1], line.outer().point(point)));
+            for p in &profile {
+                prop_assert!(finite(*p));
+                prop_assert!(p.sub(end).dot(out) > -TOLERANCE);
+                if let CapStyle::Round { .. } = cap {
+                    prop_assert!((p.sub(end).norm() - thickness / 2.0).abs() < TOLERANCE);
+                }
+            }
+        }
+    }
+}
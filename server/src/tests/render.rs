@@ -0,0 +1,54 @@
+//! The previews drawn on the CPU.
+
+use crate::geom::{Point3d, Tri3d, TriSink};
+use crate::render::{Bounds, Camera, Image, Renderer};
+use std::time::{Duration, Instant};
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+
+// a triangle round the middle of the picture, flat on the ground
+fn triangle() -> Tri3d {
+    let p = Point3d::new;
+    Tri3d::new(
+        p(-1.0, -1.0, 0.0),
+        p(1.0, -1.0, 0.0),
+        p(0.0, 1.0, 0.0),
+        p(0.0, 0.0, 1.0),
+    )
+}
+
+// measures `tris` then draws them, the way the server does with the model
+fn render(tris: &[Tri3d], camera: &Camera, deadline: Option<Instant>) -> Option<Image> {
+    let mut bounds = Bounds::default();
+    bounds.push_all(tris.to_vec());
+    let mut renderer = Renderer::new(&bounds, camera, deadline);
+    renderer.push_all(tris.to_vec());
+    renderer.finish()
+}
+
+#[test]
+fn single_triangle() {
+    let camera = Camera {
+        width: 33,
+        height: 21,
+        ..Camera::default()
+    };
+    let image = render(&[triangle()], &camera, None).unwrap();
+    assert_eq!(image.pixels.len(), 33 * 21 * 3);
+    let pixel = |x: usize, y: usize| {
+        let i = (y * 33 + x) * 3;
+        [image.pixels[i], image.pixels[i + 1], image.pixels[i + 2]]
+    };
+    assert_ne!(pixel(16, 10), BACKGROUND);
+    for &(x, y) in &[(0, 0), (32, 0), (0, 20), (32, 20)] {
+        assert_eq!(pixel(x, y), BACKGROUND, "at {}, {}", x, y);
+    }
+}
+
+#[test]
+fn gives_up_at_the_deadline() {
+    let past = Instant::now() - Duration::from_secs(1);
+    assert!(render(&[triangle()], &Camera::default(), Some(past)).is_none());
+    // with nothing to draw, there's nothing to give up on
+    assert!(render(&[], &Camera::default(), Some(past)).is_some());
+}
@@ -0,0 +1,46 @@
+//! The binary STL files models are written as.
+
+use crate::geom::{Point3d, Tri3d, TriSink};
+use crate::stl::StlWriter;
+use std::io::Cursor;
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+#[test]
+fn two_triangles() {
+    let p = Point3d::new;
+    let up = p(0.0, 0.0, 1.0);
+    let mut writer = StlWriter::new(Cursor::new(vec![])).unwrap();
+    writer.push(Tri3d::new(
+        p(1.0, 2.0, 3.0),
+        p(4.0, 5.0, 6.0),
+        p(7.0, 8.5, -9.0),
+        up,
+    ));
+    writer.push(Tri3d::new(
+        p(0.0, 0.0, 0.0),
+        p(1.0, 0.0, 0.0),
+        p(0.0, 1.0, 0.0),
+        up,
+    ));
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // the header, the count, and 50 bytes for each triangle
+    assert_eq!(bytes.len(), 84 + 2 * 50);
+    assert!(bytes[..80].iter().all(|&b| b == 0));
+    assert_eq!(
+        u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]),
+        2
+    );
+    // the normal, then the corners in order
+    let first = floats(&bytes[84..84 + 48]);
+    assert_eq!(&first[..3], &[0.0, 0.0, 1.0]);
+    assert_eq!(&first[3..6], &[1.0, 2.0, 3.0]);
+    assert_eq!(&first[9..], &[7.0, 8.5, -9.0]);
+    assert_eq!(&bytes[84 + 48..84 + 50], &[0, 0]);
+}
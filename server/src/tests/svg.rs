@@ -0,0 +1,67 @@
+//! The SVG outlines are drawn as.
+
+use crate::geom::Point2d;
+use crate::outline::{Kind, LayerOutline, Part, Path};
+use crate::svg::layers_svg;
+use std::f64::consts::PI;
+
+// the SVG of one layer, with a single path going `parts` from `start`
+fn svg(start: (f64, f64), parts: Vec<Part>) -> String {
+    let path = Path {
+        start: Point2d::new(start.0, start.1),
+        parts,
+    };
+    let layer = LayerOutline {
+        z: 2.5,
+        paths: vec![(Kind::CurvedCentre, vec![path])],
+    };
+    layers_svg(&[(3, layer)])
+}
+
+fn arc(end: (f64, f64), sweep: f64) -> Part {
+    Part::Arc {
+        end: Point2d::new(end.0, end.1),
+        centre: Point2d::new(0.0, 0.0),
+        sweep,
+    }
+}
+
+#[test]
+fn layers_are_groups() {
+    let svg = svg((0.0, 0.0), vec![Part::Line(Point2d::new(2.0, 1.0))]);
+    assert!(svg.contains(r#"<g id="layer-3" data-z="2.5">"#));
+    assert!(svg.contains(r#"<path class="curved-centre" d="M0 0 L2 1"/>"#));
+    // y goes up, so the view box starts at the top, which is -y
+    assert!(svg.contains(r#"viewBox="-0.1 -1.1 2.2 1.2""#), "{}", svg);
+    assert!(svg.contains(r#"<g transform="scale(1, -1)">"#));
+}
+
+#[test]
+fn arcs_go_the_right_way_round() {
+    // anticlockwise is the positive direction, which is a sweep flag of 1
+    let svg_anticlockwise = svg((1.0, 0.0), vec![arc((0.0, 1.0), PI / 2.0)]);
+    assert!(svg_anticlockwise.contains(r#"d="M1 0 A1 1 0 0 1 0 1""#));
+    let svg_clockwise = svg((0.0, 1.0), vec![arc((1.0, 0.0), -PI / 2.0)]);
+    assert!(svg_clockwise.contains(r#"d="M0 1 A1 1 0 0 0 1 0""#));
+}
+
+#[test]
+fn long_arcs_are_split_in_half() {
+    // three quarters of the way round, through the top left on the way
+    let svg_long = svg((1.0, 0.0), vec![arc((0.0, -1.0), 1.5 * PI)]);
+    assert!(
+        svg_long.contains(r#"d="M1 0 A1 1 0 0 1 -0.707107 0.707107 A1 1 0 0 1 0 -1""#),
+        "{}",
+        svg_long
+    );
+    // and the same the other way round, through the bottom left
+    let svg_back = svg((1.0, 0.0), vec![arc((0.0, 1.0), -1.5 * PI)]);
+    assert!(
+        svg_back.contains(r#"d="M1 0 A1 1 0 0 0 -0.707107 -0.707107 A1 1 0 0 0 0 1""#),
+        "{}",
+        svg_back
+    );
+    // exactly half way round doesn't need splitting
+    let svg_half = svg((1.0, 0.0), vec![arc((-1.0, 0.0), PI)]);
+    assert!(svg_half.contains(r#"d="M1 0 A1 1 0 0 1 -1 0""#), "{}", svg_half);
+}
@@ -0,0 +1,28 @@
+//! How thick layers are along their length.
+
+use crate::geom::*;
+use crate::{thicken_layers, Data, Params};
+use serde_json::json;
+
+#[test]
+fn peaks_inside_a_line_are_kept() {
+    let data: Data = serde_json::from_value(json!({
+        "holes": "None",
+        "thickness_profile": {"points": [[0.0, 1.0], [0.5, 3.0], [1.0, 1.0]]},
+    }))
+    .unwrap();
+    let params = Params {
+        thicken: true,
+        ..Params::default()
+    };
+    let line = Line3d::new(Point3d::new(0.0, 0.0, 1.0), Point3d::new(4.0, 0.0, 1.0));
+    let thick = thicken_layers(&[Layer::new(vec![line])], &data, &params).remove(0);
+    let width = |p: Point3d| p.y.abs();
+
+    // the line is cut at the peak, where it's three times as wide as at the ends
+    assert_eq!(thick.count(), 2);
+    let (first, second) = (thick.first(), thick.last());
+    assert!((first.end().x - 2.0).abs() < 1e-9);
+    assert!((width(first.outer().end()) - 3.0 * width(first.outer().start())).abs() < 1e-9);
+    assert!((width(second.inner().start()) - 3.0 * width(second.inner().end())).abs() < 1e-9);
+}
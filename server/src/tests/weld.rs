@@ -0,0 +1,123 @@
+//! How `weld` joins up pieces of a mesh that touch.
+
+use crate::geom::*;
+use crate::simple::{apply_symmetry, Symmetry};
+use std::collections::HashMap;
+
+// the two triangles of the quad `corners` (anticlockwise seen from outside),
+// split along the diagonal from the first corner or the second
+fn quad(corners: [Point3d; 4], from_first: bool) -> Vec<Tri3d> {
+    let [a, b, c, d] = corners;
+    let tri = |p: Point3d, q: Point3d, r: Point3d| Tri3d::from_sp(&Line3d::new(p, q), &r, false);
+    if from_first {
+        vec![tri(a, b, c), tri(a, c, d)]
+    } else {
+        vec![tri(a, b, d), tri(b, c, d)]
+    }
+}
+
+// a unit square box with its lowest corner at `x` along the x axis, `height`
+// high, with the faces at each end split from their first or second corner
+fn cuboid(x: f64, height: f64, low_end: bool, high_end: bool) -> Vec<Tri3d> {
+    let p = |i: f64, j: f64, k: f64| Point3d::new(x + i, j, k * height);
+    let faces = [
+        (
+            [p(0., 0., 0.), p(0., 0., 1.), p(0., 1., 1.), p(0., 1., 0.)],
+            low_end,
+        ),
+        (
+            [p(1., 0., 0.), p(1., 1., 0.), p(1., 1., 1.), p(1., 0., 1.)],
+            high_end,
+        ),
+        ([p(0., 0., 0.), p(1., 0., 0.), p(1., 0., 1.), p(0., 0., 1.)], true),
+        ([p(0., 1., 0.), p(0., 1., 1.), p(1., 1., 1.), p(1., 1., 0.)], true),
+        ([p(0., 0., 0.), p(0., 1., 0.), p(1., 1., 0.), p(1., 0., 0.)], true),
+        ([p(0., 0., 1.), p(1., 0., 1.), p(1., 1., 1.), p(0., 1., 1.)], true),
+    ];
+    faces
+        .iter()
+        .flat_map(|&(corners, first)| quad(corners, first))
+        .collect()
+}
+
+fn cube(x: f64, low_end: bool, high_end: bool) -> Vec<Tri3d> {
+    cuboid(x, 1.0, low_end, high_end)
+}
+
+// whether every side is shared by exactly two triangles, going opposite ways
+// along it, which makes the mesh closed and manifold
+pub(super) fn closed(tris: &[Tri3d]) -> bool {
+    let key = |p: Point3d| {
+        let k = |x: f64| (x * 1e6).round() as i64;
+        (k(p.x), k(p.y), k(p.z))
+    };
+    let mut sides = HashMap::new();
+    for t in tris {
+        for &(p, q) in &[(t.a, t.b), (t.b, t.c), (t.c, t.a)] {
+            *sides.entry((key(p), key(q))).or_insert(0) += 1;
+        }
+    }
+    sides
+        .iter()
+        .all(|(&(p, q), &n)| n == 1 && sides.get(&(q, p)) == Some(&1))
+}
+
+pub(super) fn volume(tris: &[Tri3d]) -> f64 {
+    tris.iter().map(|t| t.a.dot(t.b.cross(t.c)) / 6.0).sum()
+}
+
+#[test]
+fn touching_faces_cancel_out() {
+    let mut tris = cube(0.0, true, true);
+    tris.extend(cube(1.0, true, true));
+    // the face between them is the same two triangles both ways round
+    let welded = weld(tris, 1e-6);
+    assert_eq!(welded.len(), 20);
+    assert!(closed(&welded));
+}
+
+#[test]
+fn differently_split_faces_cancel_out() {
+    // the faces where the cubes meet cover the same square, but are split along
+    // different diagonals
+    let mut tris = cube(0.0, true, true);
+    tris.extend(cube(1.0, false, true));
+    let welded = weld(tris, 1e-6);
+    assert!(closed(&welded));
+    assert!((volume(&welded) - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn partly_touching_faces() {
+    // the cube only covers the bottom half of the end of the taller box, so
+    // what's left of that is cut to fit round it
+    let mut tris = cube(0.0, true, false);
+    tris.extend(cuboid(1.0, 2.0, true, true));
+    let welded = weld(tris, 1e-6);
+    assert!(closed(&welded));
+    assert!((volume(&welded) - 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn duplicates_are_kept_once() {
+    let mut tris = cube(0.0, true, true);
+    tris.extend(cube(0.0, true, true));
+    let welded = weld(tris, 1e-6);
+    assert_eq!(welded.len(), 12);
+    assert!(closed(&welded));
+}
+
+
+#[test]
+fn copies_make_one_solid() {
+    // four copies of a cube in the corner of the axes fill a square round them,
+    // and the faces where they meet are split along different diagonals
+    let symmetry = Symmetry {
+        copies: 4,
+        centre: [0.0, 0.0],
+        mirror: None,
+    };
+    let welded = apply_symmetry(cube(0.0, true, false), &symmetry);
+    assert!(closed(&welded));
+    assert!((volume(&welded) - 4.0).abs() < 1e-9);
+}
@@ -10,6 +10,9 @@ export class Simple3D {
     private axiom: string
     private rules: Map<string, Array<string>>
     private commands: Map<string, string>
+    // parents[i][k] is the line of layer i - 1 that line k of layer i grew from,
+    // as filled in by runN (parents[0] means nothing)
+    parents: number[][] = []
 
     constructor(axiom: string, rules: Map<string, string[]>, commands: Map<string, string>) {
         this.rules = rules
@@ -29,6 +32,11 @@ export class Simple3D {
         layers.push(this.drawLayer(this.axiom, new State(0, firstLayerLineLength / scaleFactor), z, 0, centre))
 
         let prevLayer = this.axiom
+        // for each symbol of prevLayer, the line it drew (or the next one drawn
+        // if it didn't draw any)
+        let prevSymbolLines: number[] = this.axiom.split("").map(() => 0)
+        let prevLineCount = layers[0].length
+        let parents: number[][] = [[]]
 
         for (let layerIndex = 1; layerIndex <= n; layerIndex++) {
             // update layer state
@@ -45,6 +53,8 @@ export class Simple3D {
 
             // the lines that will make up the next plane of the wireframe
             let layer: Line3d[] = []
+            let layerParents: number[] = []
+            let symbolLines: number[] = []
 
             // draw 'evolution' of each previous symbol
             for (let prevIndex = 0; prevIndex < prevLayer.length; prevIndex++) {
@@ -56,10 +66,13 @@ export class Simple3D {
                 currLayer += newSymbols.join("")
                 // draw each of the new symbols as a wireframe on the xy plane z = currPos.
                 // with the appropriate drawing state, scale, etc.
+                let parent = Math.max(0, Math.min(prevSymbolLines[prevIndex], prevLineCount - 1))
                 for (let symbol of newSymbols) {
 
                     let newLines = this.drawSymbol(state, symbol, currPos, prevIndex)
+                    symbolLines.push(layer.length)
                     layer.push(...newLines)
+                    layerParents.push(...newLines.map(() => parent))
                     if (newLines.length > 0) {
                         currPos = newLines[newLines.length - 1].end
                     }
@@ -69,10 +82,15 @@ export class Simple3D {
                 layer = centreLayer(layer)
             }
             layers.push(layer)
+            parents.push(layerParents)
             prevLayer = currLayer
+            prevSymbolLines = symbolLines
+            prevLineCount = layer.length
         }
 
         layers.splice(0, 1)
+        parents.splice(0, 1)
+        this.parents = parents
 
         return layers
 
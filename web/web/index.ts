@@ -68,7 +68,12 @@ async function runE(download: boolean) {
     let small_layers = layers.map((layer) =>
         layer.map((line) => [line.start.x, line.start.y, line.start.z, line.end.x, line.end.y, line.end.z])
     )
-    let data = [small_layers, settings["hole"]]
+    // each line's parent lets the server join it to the line it grew from
+    let data = {
+        layers: small_layers,
+        holes: settings["hole"],
+        correspondence: { Symbol: { parents: fractalGenerator.parents } },
+    }
 
     let msgpack = encode(data)
 